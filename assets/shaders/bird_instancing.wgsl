@@ -0,0 +1,59 @@
+#import bevy_pbr::mesh_types
+#import bevy_pbr::mesh_view_bindings
+
+@group(1) @binding(0)
+var<uniform> mesh: Mesh;
+
+// NOTE: Bindings must come before functions that use them!
+#import bevy_pbr::mesh_functions
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+
+    @location(7) i_pos_scale: vec4<f32>,
+    @location(8) i_rotation: vec4<f32>,
+    @location(9) i_color: vec4<f32>,
+    @location(10) i_wing_angle: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+// Rotates `v` by the unit quaternion `q`
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    let t = 2.0 * cross(q.xyz, v);
+    return v + q.w * t + cross(q.xyz, t);
+}
+
+// Swings the half of the bird on `side` of its length, the z axis, `angle` upwards.
+// The wings span the x axis, so their tips move the most and the body barely at all.
+fn flap(v: vec3<f32>, side: f32, angle: f32) -> vec3<f32> {
+    let c = cos(angle);
+    let s = sin(angle) * side;
+    return vec3<f32>(c * v.x - s * v.y, s * v.x + c * v.y, v.z);
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let side = sign(vertex.position.x);
+    let local_position = flap(vertex.position, side, vertex.i_wing_angle);
+    let local_normal = flap(vertex.normal, side, vertex.i_wing_angle);
+    let position = rotate(vertex.i_rotation, local_position * vertex.i_pos_scale.w) + vertex.i_pos_scale.xyz;
+    let normal = rotate(vertex.i_rotation, local_normal);
+
+    // Light from above plus some ambient, enough to read the shape of the wings
+    let light = 0.45 + 0.55 * max(normal.y, 0.0);
+
+    var out: VertexOutput;
+    out.clip_position = mesh_position_local_to_clip(mesh.model, vec4<f32>(position, 1.0));
+    out.color = vec4<f32>(vertex.i_color.rgb * light, vertex.i_color.a);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,83 @@
+//! Times the flocking pipeline headlessly, so changes to the grid or the steering rules can be compared.
+//!
+//! Run with `cargo bench --bench flocking`. Every result is reported in boids per second.
+
+use bevy::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use bevy_boid_birds::headless::{flock_state, headless_app, BoidSettings, FlockSnapshot, GridMap};
+
+const FLOCK_SIZES: [u32; 3] = [1_000, 10_000, 50_000];
+
+/// A headless app with `bird_count` boids that have been spawned and stepped once.
+fn flock(bird_count: u32) -> App {
+    let settings = BoidSettings {
+        bird_count,
+        predator_count: 0,
+        deterministic: true,
+        ..Default::default()
+    };
+    let mut app = headless_app(settings);
+    // The first update spawns the flock, the second steers it for the first time
+    app.update();
+    app.update();
+    app
+}
+
+fn simulation_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simulation_step");
+    group.sample_size(10);
+
+    for bird_count in FLOCK_SIZES {
+        let mut app = flock(bird_count);
+        group.throughput(Throughput::Elements(bird_count as u64));
+        group.bench_function(BenchmarkId::from_parameter(bird_count), |b| b.iter(|| app.update()));
+    }
+    group.finish();
+}
+
+fn grid_rebuild(c: &mut Criterion) {
+    let mut group = c.benchmark_group("grid_rebuild");
+
+    for bird_count in FLOCK_SIZES {
+        let mut app = flock(bird_count);
+        let settings = app.world.resource::<BoidSettings>().clone();
+        let boids = flock_state(&mut app);
+
+        group.throughput(Throughput::Elements(bird_count as u64));
+        group.bench_function(BenchmarkId::from_parameter(bird_count), |b| {
+            b.iter(|| {
+                let mut grid = GridMap::new(settings.bounds, settings.dimensions);
+                for (entity, pos, _) in &boids {
+                    grid.insert(*entity, *pos);
+                }
+                black_box(grid)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn neighbour_query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbour_query");
+
+    for bird_count in FLOCK_SIZES {
+        let app = flock(bird_count);
+        let perception = app.world.resource::<BoidSettings>().perception();
+        let grid = app.world.resource::<GridMap>();
+        let snapshot = app.world.resource::<FlockSnapshot>();
+
+        group.throughput(Throughput::Elements(bird_count as u64));
+        group.bench_function(BenchmarkId::from_parameter(bird_count), |b| {
+            b.iter(|| {
+                snapshot.boids().iter()
+                    .map(|boid| snapshot.neighbours(grid, boid.entity, boid.position, boid.velocity, perception).count())
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, simulation_step, grid_rebuild, neighbour_query);
+criterion_main!(benches);
@@ -0,0 +1,195 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::actions::game_control::GameControl;
+use crate::ron_file::{self, RonFileError};
+
+/// One physical input that can trigger a [`GameControl`].
+#[derive(Reflect, FromReflect, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    /// One direction of a stick or trigger axis, on any gamepad
+    GamepadAxis { axis: GamepadAxisType, positive: bool },
+}
+
+/// Which inputs trigger each [`GameControl`]. Loaded from [`BINDINGS_FILE`] when the app starts
+/// and again whenever the file changes, and can also be edited in the world inspector.
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct InputBindings {
+    #[serde(serialize_with = "in_control_order")]
+    pub controls: HashMap<GameControl, Vec<InputBinding>>,
+}
+
+/// Writes the controls in the order they are declared, so the file reads the same every time it is saved
+fn in_control_order<S: Serializer>(controls: &HashMap<GameControl, Vec<InputBinding>>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(controls.iter().collect::<BTreeMap<_, _>>())
+}
+
+/// File the bindings are read from, in the working directory
+pub const BINDINGS_FILE: &str = "bindings.ron";
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use GameControl::*;
+        use InputBinding::*;
+
+        let stick = |axis, positive| GamepadAxis { axis, positive };
+
+        Self {
+            controls: HashMap::from_iter([
+                (FlyForward, vec![Key(KeyCode::W), Key(KeyCode::Up), stick(GamepadAxisType::LeftStickY, true)]),
+                (FlyBackward, vec![Key(KeyCode::S), Key(KeyCode::Down), stick(GamepadAxisType::LeftStickY, false)]),
+                (FlyLeft, vec![Key(KeyCode::A), Key(KeyCode::Left), stick(GamepadAxisType::LeftStickX, false)]),
+                (FlyRight, vec![Key(KeyCode::D), Key(KeyCode::Right), stick(GamepadAxisType::LeftStickX, true)]),
+                (FlyUp, vec![Key(KeyCode::Space), GamepadButton(GamepadButtonType::RightTrigger2)]),
+                (FlyDown, vec![Key(KeyCode::LShift), GamepadButton(GamepadButtonType::LeftTrigger2)]),
+                (LookLeft, vec![stick(GamepadAxisType::RightStickX, false)]),
+                (LookRight, vec![stick(GamepadAxisType::RightStickX, true)]),
+                (LookUp, vec![stick(GamepadAxisType::RightStickY, true)]),
+                (LookDown, vec![stick(GamepadAxisType::RightStickY, false)]),
+                (ToggleCursor, vec![Key(KeyCode::Escape), GamepadButton(GamepadButtonType::Select)]),
+                (Scatter, vec![Key(KeyCode::X), GamepadButton(GamepadButtonType::South)]),
+                (Attract, vec![Key(KeyCode::F), Mouse(MouseButton::Right), GamepadButton(GamepadButtonType::West)]),
+                (GrowFlock, vec![Key(KeyCode::Equals), Key(KeyCode::NumpadAdd), GamepadButton(GamepadButtonType::DPadUp)]),
+                (ShrinkFlock, vec![Key(KeyCode::Minus), Key(KeyCode::NumpadSubtract), GamepadButton(GamepadButtonType::DPadDown)]),
+                (CycleCamera, vec![Key(KeyCode::C), GamepadButton(GamepadButtonType::North)]),
+                (SelectBird, vec![Mouse(MouseButton::Left), GamepadButton(GamepadButtonType::East)]),
+                (NextBird, vec![Key(KeyCode::Tab), Key(KeyCode::RBracket), GamepadButton(GamepadButtonType::RightTrigger)]),
+                (PreviousBird, vec![Key(KeyCode::LBracket), GamepadButton(GamepadButtonType::LeftTrigger)]),
+                (SaveFlock, vec![Key(KeyCode::F5)]),
+                (LoadFlock, vec![Key(KeyCode::F9)]),
+                (ToggleRecording, vec![Key(KeyCode::F8)]),
+                (ToggleGridOverlay, vec![Key(KeyCode::F1)]),
+                (ToggleVelocityOverlay, vec![Key(KeyCode::F2)]),
+                (TogglePerceptionOverlay, vec![Key(KeyCode::F3)]),
+                (ToggleBoundsOverlay, vec![Key(KeyCode::F4)]),
+            ]),
+        }
+    }
+}
+
+impl InputBindings {
+    /// The inputs bound to `control`
+    pub fn get(&self, control: GameControl) -> &[InputBinding] {
+        self.controls.get(&control).map_or(&[], Vec::as_slice)
+    }
+
+    /// Reads the bindings from a RON file. Controls the file leaves out keep their default bindings.
+    pub fn load(path: &Path) -> Result<Self, RonFileError> {
+        let loaded: Self = ron_file::load(path)?;
+        let mut bindings = Self::default();
+        bindings.controls.extend(loaded.controls);
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RonFileError> {
+        ron_file::save(self, path)
+    }
+}
+
+/// Where the bindings were loaded from, watched for changes.
+#[derive(Resource)]
+pub(super) struct BindingsFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    poll: Timer,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl BindingsFile {
+    /// Loads the bindings from `path`. When there is no such file yet and `write_defaults` is set,
+    /// the defaults are written to it as a starting point for editing them.
+    pub(super) fn load(path: impl Into<PathBuf>, write_defaults: bool) -> (Self, InputBindings) {
+        let path = path.into();
+        let bindings = match modified(&path).map(|_| InputBindings::load(&path)) {
+            Some(Ok(bindings)) => bindings,
+            Some(Err(err)) => {
+                error!("Could not load the input bindings from {}: {}", path.display(), err);
+                InputBindings::default()
+            }
+            None => {
+                let bindings = InputBindings::default();
+                if write_defaults {
+                    match bindings.save(&path) {
+                        Ok(()) => info!("Wrote the default input bindings to {}", path.display()),
+                        Err(err) => error!("Could not write the default input bindings to {}: {}", path.display(), err),
+                    }
+                }
+                bindings
+            }
+        };
+        // Read after any save, so writing the defaults does not count as a change to reload
+        let modified = modified(&path);
+
+        (Self { path, modified, poll: Timer::from_seconds(1., TimerMode::Repeating) }, bindings)
+    }
+}
+
+/// Reloads the bindings whenever the file is saved, so they can be changed while the app runs.
+pub(super) fn reload_bindings (
+    mut file: ResMut<BindingsFile>,
+    mut bindings: ResMut<InputBindings>,
+    time: Res<Time>,
+) {
+    if !file.poll.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let modified = modified(&file.path);
+    if modified.is_none() || modified == file.modified {
+        return;
+    }
+    file.modified = modified;
+
+    match InputBindings::load(&file.path) {
+        Ok(loaded) => {
+            *bindings = loaded;
+            info!("Reloaded input bindings from {}", file.path.display());
+        }
+        Err(err) => error!("Could not reload the input bindings from {}: {}", file.path.display(), err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_defaults_load_back_unchanged() {
+        let path = std::env::temp_dir().join(format!("bindings-{}.ron", std::process::id()));
+        InputBindings::default().save(&path).unwrap();
+        let loaded = InputBindings::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), InputBindings::default());
+    }
+
+    #[test]
+    fn controls_left_out_of_the_file_keep_their_defaults() {
+        let mut bindings = InputBindings::default();
+        bindings.controls.retain(|control, _| *control == GameControl::Scatter);
+        bindings.controls.insert(GameControl::Scatter, vec![InputBinding::Key(KeyCode::Z)]);
+
+        let path = std::env::temp_dir().join(format!("partial-bindings-{}.ron", std::process::id()));
+        bindings.save(&path).unwrap();
+        let loaded = InputBindings::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.get(GameControl::Scatter), &[InputBinding::Key(KeyCode::Z)]);
+        assert_eq!(loaded.get(GameControl::Attract), InputBindings::default().get(GameControl::Attract));
+    }
+}
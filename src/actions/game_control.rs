@@ -1,35 +1,98 @@
-use bevy::prelude::{Input, KeyCode, Res};
+use std::marker::PhantomData;
 
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::actions::bindings::{InputBinding, InputBindings};
+use crate::actions::PointerOverUi;
+
+/// Named controls the player can bind inputs to, see [`InputBindings`].
+#[derive(Reflect, FromReflect, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum GameControl {
-    Up,
-    Down,
-    Left,
-    Right,
+    FlyForward,
+    FlyBackward,
+    FlyLeft,
+    FlyRight,
+    FlyUp,
+    FlyDown,
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+    ToggleCursor,
+    Scatter,
+    Attract,
+    GrowFlock,
+    ShrinkFlock,
+    CycleCamera,
+    SelectBird,
+    NextBird,
+    PreviousBird,
+    SaveFlock,
+    LoadFlock,
+    ToggleRecording,
+    ToggleGridOverlay,
+    ToggleVelocityOverlay,
+    TogglePerceptionOverlay,
+    ToggleBoundsOverlay,
 }
 
-impl GameControl {
-    pub fn pressed(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
-        match self {
-            GameControl::Up => {
-                keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up)
-            }
-            GameControl::Down => {
-                keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down)
-            }
-            GameControl::Left => {
-                keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left)
-            }
-            GameControl::Right => {
-                keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right)
-            }
-        }
-    }
+/// How far an axis has to be pushed before its binding counts as pressed
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+/// Reads [`GameControl`]s from every bound keyboard, mouse and gamepad input.
+/// Mouse buttons are ignored while the pointer is over the UI, whose widgets they are meant for.
+#[derive(SystemParam)]
+pub struct ControlInput<'w, 's> {
+    bindings: Res<'w, InputBindings>,
+    keys: Res<'w, Input<KeyCode>>,
+    mouse_buttons: Res<'w, Input<MouseButton>>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+    gamepads: Res<'w, Gamepads>,
+    over_ui: Res<'w, PointerOverUi>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
 }
 
-pub fn get_movement(control: GameControl, input: &Res<Input<KeyCode>>) -> f32 {
-    if control.pressed(input) {
-        1.0
-    } else {
-        0.0
+impl ControlInput<'_, '_> {
+    /// How far `control` is pushed, from 0 to 1. Buttons and keys are either 0 or 1.
+    pub fn value(&self, control: GameControl) -> f32 {
+        self.bindings.get(control).iter()
+            .map(|binding| self.binding_value(*binding))
+            .fold(0., f32::max)
+    }
+
+    pub fn pressed(&self, control: GameControl) -> bool {
+        self.value(control) >= AXIS_PRESS_THRESHOLD
+    }
+
+    /// Whether a key or button bound to `control` went down this frame. Axes never count as just pressed.
+    pub fn just_pressed(&self, control: GameControl) -> bool {
+        self.bindings.get(control).iter().any(|binding| match *binding {
+            InputBinding::Key(key) => self.keys.just_pressed(key),
+            InputBinding::Mouse(button) => !self.over_ui.0 && self.mouse_buttons.just_pressed(button),
+            InputBinding::GamepadButton(button_type) => self.gamepads.iter()
+                .any(|gamepad| self.gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type))),
+            InputBinding::GamepadAxis { .. } => false,
+        })
+    }
+
+    fn binding_value(&self, binding: InputBinding) -> f32 {
+        let held = |pressed: bool| if pressed { 1. } else { 0. };
+
+        match binding {
+            InputBinding::Key(key) => held(self.keys.pressed(key)),
+            InputBinding::Mouse(button) => held(!self.over_ui.0 && self.mouse_buttons.pressed(button)),
+            InputBinding::GamepadButton(button_type) => held(self.gamepads.iter()
+                .any(|gamepad| self.gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type)))),
+            InputBinding::GamepadAxis { axis, positive } => {
+                let sign = if positive { 1. } else { -1. };
+                self.gamepads.iter()
+                    .filter_map(|gamepad| self.gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+                    .map(|value| (value * sign).clamp(0., 1.))
+                    .fold(0., f32::max)
+            }
+        }
     }
 }
@@ -1,38 +1,100 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, ui::UiSystem, utils::HashMap, window::WindowId};
+use bevy_inspector_egui::bevy_egui::{EguiContext, EguiSystem};
 
-use crate::actions::game_control::{get_movement, GameControl};
+use crate::actions::bindings::{reload_bindings, BindingsFile};
 use crate::GameState;
 
+mod bindings;
 mod game_control;
 
-pub struct ActionsPlugin;
+pub use bindings::{InputBinding, InputBindings, BINDINGS_FILE};
+pub use game_control::{ControlInput, GameControl};
 
-// This plugin listens for keyboard input and converts the input into Actions
-// Actions can then be used as a resource in other systems to act on the player input.
+#[derive(Default)]
+pub struct ActionsPlugin {
+    /// Writes the default bindings to [`BINDINGS_FILE`] when it does not exist yet. Only the game
+    /// sets this, so tests and tools using the plugin leave the working directory alone.
+    pub write_default_bindings: bool,
+}
+
+// This plugin listens for keyboard, mouse and gamepad input and converts it into Actions
+// through the InputBindings. Actions can then be used as a resource in other systems to act on the player input.
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Actions>().add_system_set(
-            SystemSet::on_update(GameState::Playing).with_system(set_movement_actions),
-        );
+        let (file, bindings) = BindingsFile::load(BINDINGS_FILE, self.write_default_bindings);
+
+        app.init_resource::<Actions>()
+            .init_resource::<PointerOverUi>()
+            .insert_resource(bindings)
+            // The inspector needs every type along the way to edit the bindings
+            .register_type::<InputBindings>()
+            .register_type::<HashMap<GameControl, Vec<InputBinding>>>()
+            .register_type::<Vec<InputBinding>>()
+            .register_type::<InputBinding>()
+            .register_type::<GameControl>()
+            .insert_resource(file)
+            .add_system(reload_bindings)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                track_pointer_over_ui.after(UiSystem::Focus).after(EguiSystem::BeginFrame),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(set_actions.after(reload_bindings)),
+            );
     }
 }
 
+/// How far in front of the camera the flock is drawn to while attracting
+const ATTRACT_DISTANCE: f32 = 40.;
+
+/// What the player wants to happen this frame, read by the boid, camera and audio plugins.
 #[derive(Default, Resource)]
 pub struct Actions {
-    pub player_movement: Option<Vec2>,
+    /// Scatter the flock
+    pub scatter: bool,
+    /// Point the flock is drawn to while the attract key is held
+    pub attract: Option<Vec3>,
+    /// Grow the flock by this many steps, or shrink it when negative
+    pub resize_flock: i32,
+    /// Switch to the next camera mode
+    pub cycle_camera: bool,
+    pub save_flock: bool,
+    pub load_flock: bool,
+    pub toggle_recording: bool,
 }
 
-pub fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<KeyCode>>) {
-    let player_movement = Vec2::new(
-        get_movement(GameControl::Right, &keyboard_input)
-            - get_movement(GameControl::Left, &keyboard_input),
-        get_movement(GameControl::Up, &keyboard_input)
-            - get_movement(GameControl::Down, &keyboard_input),
-    );
+/// Whether the pointer is over the egui inspector or a UI panel, so mouse buttons are meant for them
+/// rather than the flock. See [`ControlInput`].
+#[derive(Default, Resource)]
+pub struct PointerOverUi(pub bool);
+
+fn track_pointer_over_ui(
+    mut over_ui: ResMut<PointerOverUi>,
+    mut egui: Option<ResMut<EguiContext>>,
+    q_interactions: Query<&Interaction>,
+) {
+    let ctx = egui.as_mut().and_then(|egui| egui.try_ctx_for_window_mut(WindowId::primary()));
+    // egui only wants the pointer once a button is down, so on the frame of a click check the area too
+    let over_egui = matches!(ctx, Some(ctx) if ctx.wants_pointer_input() || ctx.is_pointer_over_area());
+    over_ui.0 = over_egui || q_interactions.iter().any(|interaction| *interaction != Interaction::None);
+}
 
-    if player_movement != Vec2::ZERO {
-        actions.player_movement = Some(player_movement.normalize());
+pub fn set_actions(
+    mut actions: ResMut<Actions>,
+    input: ControlInput,
+    q_camera: Query<&GlobalTransform, With<Camera>>,
+) {
+    actions.scatter = input.just_pressed(GameControl::Scatter);
+    actions.attract = if input.pressed(GameControl::Attract) {
+        q_camera.iter().next().map(|camera| camera.translation() + camera.forward() * ATTRACT_DISTANCE)
     } else {
-        actions.player_movement = None;
-    }
+        None
+    };
+    actions.resize_flock = input.just_pressed(GameControl::GrowFlock) as i32
+        - input.just_pressed(GameControl::ShrinkFlock) as i32;
+    actions.save_flock = input.just_pressed(GameControl::SaveFlock);
+    actions.load_flock = input.just_pressed(GameControl::LoadFlock);
+    actions.toggle_recording = input.just_pressed(GameControl::ToggleRecording);
+    actions.cycle_camera = input.just_pressed(GameControl::CycleCamera);
 }
@@ -1,4 +1,4 @@
-use crate::actions::{set_movement_actions, Actions};
+use crate::actions::{set_actions, Actions};
 use crate::loading::AudioAssets;
 use crate::GameState;
 use bevy::prelude::*;
@@ -11,14 +11,16 @@ impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(AudioPlugin)
             .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_audio))
-            // .add_system_set(
-            //     SystemSet::on_update(GameState::Playing)
-            //         .with_system(control_flying_sound.after(set_movement_actions)),
-            // )
-            ;
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(control_flying_sound.after(set_actions)),
+            );
     }
 }
 
+/// Seconds the wing beats keep sounding after the flock is scattered
+const SCATTER_SOUND_TIME: f32 = 2.;
+
 #[derive(Resource)]
 struct FlyingAudio(Handle<AudioInstance>);
 
@@ -32,20 +34,29 @@ fn start_audio(mut commands: Commands, audio_assets: Res<AudioAssets>, audio: Re
     commands.insert_resource(FlyingAudio(handle));
 }
 
+/// Plays the wing beats while the player stirs up the flock, by scattering or attracting it.
 fn control_flying_sound(
     actions: Res<Actions>,
     audio: Res<FlyingAudio>,
     mut audio_instances: ResMut<Assets<AudioInstance>>,
+    mut scatter_left: Local<f32>,
+    time: Res<Time>,
 ) {
+    if actions.scatter {
+        *scatter_left = SCATTER_SOUND_TIME;
+    }
+    *scatter_left = (*scatter_left - time.delta_seconds()).max(0.);
+    let stirred = *scatter_left > 0. || actions.attract.is_some();
+
     if let Some(instance) = audio_instances.get_mut(&audio.0) {
         match instance.state() {
             PlaybackState::Paused { .. } => {
-                if actions.player_movement.is_some() {
+                if stirred {
                     instance.resume(AudioTween::default());
                 }
             }
             PlaybackState::Playing { .. } => {
-                if actions.player_movement.is_none() {
+                if !stirred {
                     instance.pause(AudioTween::default());
                 }
             }
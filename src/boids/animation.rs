@@ -0,0 +1,138 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use super::{Boid, BoidSettings, Species, TargetVelocity, Velocity};
+use crate::loading::SceneAssets;
+
+/// The animation player inside a boid's scene, once the scene has been spawned.
+#[derive(Component)]
+pub struct FlightAnimation {
+    pub player: Entity,
+}
+
+/// Starts the flight clip on every animation player that appears inside a boid's scene,
+/// at a random point of the wing beat so the flock does not flap in lockstep.
+pub(super) fn start_flight_animations (
+    mut commands: Commands,
+    mut q_players: Query<(Entity, &mut AnimationPlayer), Added<AnimationPlayer>>,
+    q_parents: Query<&Parent>,
+    q_boids: Query<&Species, With<Boid>>,
+    scenes: Res<SceneAssets>,
+    clips: Res<Assets<AnimationClip>>,
+) {
+    // Purely visual, so it does not draw from the seeded simulation RNG
+    let mut rng = rand::thread_rng();
+
+    for (player_entity, mut player) in q_players.iter_mut() {
+        // The player sits somewhere below the boid in the scene hierarchy
+        let mut ancestor = player_entity;
+        let boid = loop {
+            if let Ok(species) = q_boids.get(ancestor) {
+                break Some((ancestor, *species));
+            }
+            match q_parents.get(ancestor) {
+                Ok(parent) => ancestor = parent.get(),
+                Err(_) => break None,
+            }
+        };
+
+        let (boid, species) = match boid {
+            Some(boid) => boid,
+            None => continue,
+        };
+
+        let clip = match species {
+            Species::Starling | Species::Gull => scenes.bird_flight.clone(),
+            Species::Pigeon => scenes.pigeon_flight.clone(),
+        };
+        let duration = clips.get(&clip).map_or(1., |clip| clip.duration());
+
+        player.play(clip).repeat();
+        player.set_elapsed(rng.gen_range(0.0..duration.max(f32::EPSILON)));
+        commands.entity(boid).insert(FlightAnimation { player: player_entity });
+    }
+}
+
+/// Fraction of the way to a new wing beat rate covered per second, so the beat does not jump
+const FLAP_RESPONSE: f32 = 4.;
+
+/// Wing beat rate relative to the normal one: faster when climbing or turning hard, gliding when descending.
+fn flap_rate(vel: Vec3, target: Vec3, settings: &BoidSettings) -> f32 {
+    // Vertical share of the velocity, which `steer_horizontal` keeps within about ±0.1
+    let climb = vel.normalize_or_zero().y;
+    // 0 flying straight to 1 steering with the full `max_force`
+    let turning = ((target - vel).length() / settings.max_force.max(f32::EPSILON)).min(1.);
+
+    (1. + 8. * climb + turning).clamp(0.2, 2.5)
+}
+
+/// Sets the speed of each boid's flight clip to its [`flap_rate`].
+pub(super) fn drive_flap_speed (
+    q_boids: Query<(&Velocity, &TargetVelocity, &FlightAnimation)>,
+    mut q_players: Query<&mut AnimationPlayer>,
+    settings: Res<BoidSettings>,
+    time: Res<Time>,
+) {
+    let blend = (FLAP_RESPONSE * time.delta_seconds()).min(1.);
+
+    for (vel, target, animation) in q_boids.iter() {
+        let mut player = match q_players.get_mut(animation.player) {
+            Ok(player) => player,
+            Err(_) => continue,
+        };
+
+        let speed = player.speed();
+        player.set_speed(speed + (flap_rate(vel.0, target.0, &settings) - speed) * blend);
+    }
+}
+
+/// Wing beats per second at a [`flap_rate`] of 1, close to the flight clips
+const WING_BEATS_PER_SECOND: f32 = 3.;
+
+/// Furthest the wings of an instanced bird swing above and below level, in radians
+const WING_AMPLITUDE: f32 = 0.6;
+
+/// The wing beat of a boid drawn by instancing, which has no skeleton to play the flight clip on.
+#[derive(Component)]
+pub struct WingBeat {
+    /// Point of the beat, in radians
+    phase: f32,
+    /// Current [`flap_rate`], eased towards the target one
+    rate: f32,
+}
+
+impl WingBeat {
+    /// Angle of the wings above level. Slower beats are also shallower, down to a glide.
+    pub fn angle(&self) -> f32 {
+        WING_AMPLITUDE * self.rate.min(1.) * self.phase.sin()
+    }
+}
+
+/// Gives new boids a wing beat at a random phase, so the flock does not flap in lockstep.
+pub(super) fn start_wing_beats (
+    mut commands: Commands,
+    q_new: Query<Entity, (With<Boid>, Without<WingBeat>)>,
+) {
+    // Purely visual, so it does not draw from the seeded simulation RNG
+    let mut rng = rand::thread_rng();
+
+    for entity in q_new.iter() {
+        commands.entity(entity).insert(WingBeat { phase: rng.gen_range(0.0..TAU), rate: 1. });
+    }
+}
+
+/// Advances every [`WingBeat`] at the boid's [`flap_rate`].
+pub(super) fn beat_wings (
+    mut q_boids: Query<(&Velocity, &TargetVelocity, &mut WingBeat)>,
+    settings: Res<BoidSettings>,
+    time: Res<Time>,
+) {
+    let blend = (FLAP_RESPONSE * time.delta_seconds()).min(1.);
+
+    for (vel, target, mut wings) in q_boids.iter_mut() {
+        wings.rate += (flap_rate(vel.0, target.0, &settings) - wings.rate) * blend;
+        wings.phase = (wings.phase + TAU * WING_BEATS_PER_SECOND * wings.rate * time.delta_seconds()) % TAU;
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{steer_towards, Boid, BoidSettings, GridMap, Obstacle, Species, SpeciesTable, SteeringForce, SteeringRule, SteeringWeights, Velocity};
+
+/// How the flock is kept inside `BoidSettings::bounds`.
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BoundaryMode {
+    /// Toroidal space, leaving through one face enters through the opposite one
+    Wrap,
+    /// Walls that push boids back once they come within `wall_margin`
+    #[default]
+    SoftWalls,
+    /// Elastic reflection off the faces of the bounds
+    Bounce,
+    /// Ball of `arena_radius` around the centre of the bounds
+    Sphere,
+    /// Vertical cylinder of `arena_radius` around the centre of the bounds, capped by the bounds' floor and ceiling
+    Cylinder,
+}
+
+/// Steers boids back from the boundary before they reach it, for the modes with soft edges.
+pub(super) fn steer_inside_bounds (
+    mut query: Query<(&Transform, &Velocity, &Species, &mut SteeringForce)>,
+    settings: Res<BoidSettings>,
+    weights: Res<SteeringWeights>,
+    table: Res<SpeciesTable>,
+) {
+    let [min, max] = settings.bounds;
+    let center = (min + max) / 2.;
+    let margin = settings.wall_margin.max(f32::EPSILON);
+
+    // How far into the margin of a wall `inside` units from it a boid is, 0 outside the margin and 1 at the wall
+    let urgency = |inside: f32| ((margin - inside) / margin).max(0.);
+
+    for (trans, vel, species, mut steering) in query.iter_mut() {
+        let pos = trans.translation;
+        let push = match settings.boundary {
+            BoundaryMode::SoftWalls => {
+                let from_min = pos - min;
+                let from_max = max - pos;
+                Vec3::new(
+                    urgency(from_min.x) - urgency(from_max.x),
+                    urgency(from_min.y) - urgency(from_max.y),
+                    urgency(from_min.z) - urgency(from_max.z),
+                )
+            }
+            BoundaryMode::Sphere => {
+                let offset = pos - center;
+                -offset.normalize_or_zero() * urgency(settings.arena_radius - offset.length())
+            }
+            BoundaryMode::Cylinder => {
+                let offset = Vec3::new(pos.x - center.x, 0., pos.z - center.z);
+                let radial = -offset.normalize_or_zero() * urgency(settings.arena_radius - offset.length());
+                radial + Vec3::Y * (urgency(pos.y - min.y) - urgency(max.y - pos.y))
+            }
+            BoundaryMode::Wrap | BoundaryMode::Bounce => continue,
+        };
+
+        if push != Vec3::ZERO {
+            let params = table.params(*species);
+            let weight = weights.bounds * params.weights.bounds * push.length().min(1.);
+            steering.add(SteeringRule::Bounds, weight, steer_towards(vel.0, push, settings.max_speed * params.speed_factor));
+        }
+    }
+}
+
+/// Enforces the hard edge of the boundary after moving, and keeps the grid in step with any teleport.
+pub(super) fn confine_to_bounds (
+    mut query: Query<(Entity, &mut Transform, &mut Velocity, Option<&Boid>), Without<Obstacle>>,
+    mut grid: ResMut<GridMap>,
+    settings: Res<BoidSettings>,
+) {
+    let [min, max] = settings.bounds;
+    let size = max - min;
+    let center = (min + max) / 2.;
+
+    for (entity, mut trans, mut vel, boid) in query.iter_mut() {
+        let prev = trans.translation;
+        let mut pos = prev;
+
+        match settings.boundary {
+            BoundaryMode::Wrap => {
+                // Only the axes that left the bounds are moved
+                for axis in 0..3 {
+                    if pos[axis] < min[axis] {
+                        pos[axis] += size[axis];
+                    } else if pos[axis] > max[axis] {
+                        pos[axis] -= size[axis];
+                    }
+                }
+            }
+            BoundaryMode::Bounce => {
+                for axis in 0..3 {
+                    if pos[axis] < min[axis] {
+                        pos[axis] = 2. * min[axis] - pos[axis];
+                        vel.0[axis] = vel.0[axis].abs();
+                    } else if pos[axis] > max[axis] {
+                        pos[axis] = 2. * max[axis] - pos[axis];
+                        vel.0[axis] = -vel.0[axis].abs();
+                    }
+                }
+            }
+            BoundaryMode::Sphere => {
+                let offset = pos - center;
+                if offset.length() > settings.arena_radius {
+                    let normal = offset.normalize();
+                    pos = center + normal * settings.arena_radius;
+                    let outward = vel.0.dot(normal).max(0.);
+                    vel.0 -= normal * outward;
+                }
+            }
+            BoundaryMode::Cylinder => {
+                let offset = Vec3::new(pos.x - center.x, 0., pos.z - center.z);
+                if offset.length() > settings.arena_radius {
+                    let normal = offset.normalize();
+                    pos -= normal * (offset.length() - settings.arena_radius);
+                    let outward = vel.0.dot(normal).max(0.);
+                    vel.0 -= normal * outward;
+                }
+                if pos.y < min.y || pos.y > max.y {
+                    pos.y = pos.y.clamp(min.y, max.y);
+                    vel.0.y = 0.;
+                }
+            }
+            BoundaryMode::SoftWalls => {}
+        }
+
+        if pos != prev {
+            trans.translation = pos;
+            // Predators are confined too, but only boids are in the grid
+            if boid.is_some() {
+                grid.move_entity(entity, prev, pos);
+            }
+        }
+    }
+}
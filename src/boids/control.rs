@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+
+use super::{
+    spawn_boid, steer_towards, Boid, BoidSettings, FlockSnapshot, GridMap, Perception, SimulationRng, SimulationStep,
+    Species, SpeciesTable, SteeringForce, SteeringRule, SteeringWeights, Velocity,
+};
+
+/// Outside intent the flock reacts to. The interactive app sets it from the player's
+/// [`Actions`](crate::actions::Actions), headless runs can set it directly.
+#[derive(Resource, Default, Debug)]
+pub struct FlockControl {
+    /// Point the flock flies towards while set
+    pub attractor: Option<Vec3>,
+    /// Seconds the flock keeps scattering for
+    pub scatter: f32,
+}
+
+/// Adds this many boids to the flock, or removes them when negative, leaving the rest where they are.
+pub struct ResizeFlock(pub i32);
+
+/// Spawns or despawns boids for [`ResizeFlock`] events. The grid is updated immediately and
+/// `bird_count` follows it, so `apply_settings_changes` does not respawn the flock.
+pub(super) fn resize_flock (
+    mut commands: Commands,
+    mut events: EventReader<ResizeFlock>,
+    mut grid: ResMut<GridMap>,
+    mut rng: ResMut<SimulationRng>,
+    mut settings: ResMut<BoidSettings>,
+    table: Res<SpeciesTable>,
+    q_boids: Query<(Entity, &Transform), With<Boid>>,
+) {
+    let change: i32 = events.iter().map(|event| event.0).sum();
+    if change == 0 {
+        return;
+    }
+
+    if change > 0 {
+        for _ in 0..change {
+            let species = table.pick(&mut rng.0);
+            spawn_boid(&mut commands, &mut grid, &mut rng, &settings, &table, species);
+        }
+    } else {
+        // The newest boids go first, so removing birds is as reproducible as adding them
+        let mut boids: Vec<_> = q_boids.iter().collect();
+        boids.sort_by_key(|(entity, _)| *entity);
+        for (entity, trans) in boids.into_iter().rev().take(change.unsigned_abs() as usize) {
+            grid.remove(entity, trans.translation);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    settings.bird_count = grid.len() as u32;
+}
+
+/// Scatters boids away from their local centre and draws them towards the attractor, per [`FlockControl`].
+#[allow(clippy::too_many_arguments)]
+pub(super) fn follow_flock_control (
+    mut query: Query<(Entity, &Transform, &Velocity, &Species, &mut SteeringForce), With<Boid>>,
+    mut control: ResMut<FlockControl>,
+    snapshot: Res<FlockSnapshot>,
+    grid: Res<GridMap>,
+    settings: Res<BoidSettings>,
+    weights: Res<SteeringWeights>,
+    table: Res<SpeciesTable>,
+    step: Res<SimulationStep>,
+) {
+    let scattering = control.scatter > 0.;
+    let attractor = control.attractor;
+    if !scattering && attractor.is_none() {
+        return;
+    }
+
+    query.par_for_each_mut(super::STEERING_BATCH_SIZE, |(entity, trans, vel, species, mut steering)| {
+        let params = table.params(*species);
+        let max_speed = settings.max_speed * params.speed_factor;
+
+        if scattering {
+            // Away from every boid around, whatever its species
+            let perception = Perception::all_around(settings.perception_radius * params.perception_factor);
+            let mut sum_offset = Vec3::ZERO;
+            let mut count = 0;
+            for (near, _) in snapshot.neighbours(&grid, entity, trans.translation, vel.0, perception) {
+                sum_offset += near.offset;
+                count += 1;
+            }
+            if count > 0 {
+                let weight = weights.scatter * params.weights.scatter;
+                steering.add(SteeringRule::Scatter, weight, steer_towards(vel.0, -sum_offset / count as f32, max_speed));
+            }
+        }
+
+        if let Some(point) = attractor {
+            let weight = weights.attract * params.weights.attract;
+            steering.add(SteeringRule::Attract, weight, steer_towards(vel.0, point - trans.translation, max_speed));
+        }
+    });
+
+    control.scatter = (control.scatter - step.delta).max(0.);
+}
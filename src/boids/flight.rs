@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+use super::BoidSettings;
+
+/// Gravitational acceleration, in units per second squared.
+pub const GRAVITY: f32 = 9.81;
+
+/// Speeds a boid may fly at, after its species' speed factor.
+#[derive(Clone, Copy, Debug)]
+pub struct SpeedRange {
+    /// Speed the boid's wing beats try to hold
+    pub cruise: f32,
+    /// Stall speed, the boid never flies slower
+    pub min: f32,
+    pub max: f32,
+}
+
+/// One step of the physical flight model: the new velocity after `steering` is applied for `delta` seconds.
+///
+/// Steering across the flight path turns the boid, while steering along it adds to the wing beats,
+/// which try to hold the cruise speed. Together they accelerate or brake by at most `max_acceleration`.
+/// Gravity slows climbs and speeds up dives, and drag grows with the square of the speed.
+pub fn fly(velocity: Vec3, steering: Vec3, speeds: SpeedRange, settings: &BoidSettings, delta: f32) -> Vec3 {
+    let speed = velocity.length();
+    let forward = velocity.try_normalize()
+        .or_else(|| steering.try_normalize())
+        .unwrap_or(Vec3::Z);
+
+    let along = steering.dot(forward);
+    let across = steering - forward * along;
+
+    let max_acceleration = settings.max_acceleration;
+    let thrust = (speeds.cruise - speed + along).clamp(-max_acceleration, max_acceleration);
+    let acceleration = thrust - GRAVITY * forward.y - settings.drag * speed * speed;
+
+    let new_speed = (speed + acceleration * delta).clamp(speeds.min, speeds.max.max(speeds.min));
+    let new_forward = (forward * speed + across * delta).try_normalize().unwrap_or(forward);
+    new_forward * new_speed
+}
+
+/// Rotation that points the model's nose along `velocity`, or the identity when standing still.
+pub fn facing(velocity: Vec3) -> Quat {
+    velocity.try_normalize().map_or(Quat::IDENTITY, |forward| Quat::from_rotation_arc(Vec3::Z, forward))
+}
+
+/// Turns `rotation` towards flying along `velocity`, banked into the turn for a lateral `acceleration`.
+///
+/// The nose follows the velocity at up to `pitch_rate` and the wings roll towards the bank
+/// angle of a coordinated turn at up to `roll_rate`. The model looks along +Z with +Y up.
+/// The roll is measured against the horizon, so when the nose points straight up or down
+/// the current roll is simply held instead of flipping.
+pub fn bank_into_turn(rotation: Quat, velocity: Vec3, acceleration: Vec3, settings: &BoidSettings, delta: f32) -> Quat {
+    let forward = match velocity.try_normalize() {
+        Some(forward) => forward,
+        None => return rotation,
+    };
+
+    // Pitch and yaw: swing the nose along the shortest arc towards the velocity
+    let nose = rotation * Vec3::Z;
+    let (axis, angle) = Quat::from_rotation_arc(nose, forward).to_axis_angle();
+    let max_turn = settings.pitch_rate.to_radians() * delta;
+    let mut rotation = (Quat::from_axis_angle(axis, angle.min(max_turn)) * rotation).normalize();
+
+    // Roll: measured around the nose, from wings level with the horizon
+    let nose = rotation * Vec3::Z;
+    let level_side = match Vec3::Y.cross(nose).try_normalize() {
+        Some(side) if nose.y.abs() < 0.999 => side,
+        _ => return rotation,
+    };
+    let level_up = nose.cross(level_side);
+    let up = rotation * Vec3::Y;
+    let roll = (-up.dot(level_side)).atan2(up.dot(level_up));
+
+    // In a coordinated turn the lift, along the up axis, tilts towards the centre of the turn
+    let max_bank = settings.max_bank.to_radians();
+    let target_roll = -(acceleration.dot(level_side) / GRAVITY).atan().clamp(-max_bank, max_bank);
+
+    let max_roll = settings.roll_rate.to_radians() * delta;
+    let roll_step = (target_roll - roll).clamp(-max_roll, max_roll);
+    rotation = (Quat::from_axis_angle(nose, roll_step) * rotation).normalize();
+
+    rotation
+}
@@ -0,0 +1,276 @@
+use bevy::prelude::*;
+
+/// Uniform spatial hash over the simulation bounds.
+///
+/// Cells live in a flat array indexed by their integer coordinate, so lookups never
+/// allocate and two different cells can never share a slot.
+#[derive(Resource)]
+pub struct GridMap {
+    bounds: [Vec3; 2],
+    cell_size: Vec3,
+    dimensions: IVec3,
+    cells: Vec<Vec<Entity>>,
+    len: usize,
+}
+
+impl GridMap {
+    /// Smallest width of the bounds on any axis
+    pub const MIN_EXTENT: f32 = 1.;
+
+    /// Most cells along any axis, which keeps the whole grid to about two million cells
+    pub const MAX_DIMENSION: i32 = 128;
+
+    /// Bounds narrower than [`MIN_EXTENT`](Self::MIN_EXTENT) on an axis are widened to it, so no cell is empty or inverted.
+    /// Dimensions are clamped to between 1 and [`MAX_DIMENSION`](Self::MAX_DIMENSION).
+    pub fn new(bounds: [Vec3; 2], dimensions: [i32; 3]) -> Self {
+        let bounds = [bounds[0], bounds[1].max(bounds[0] + Vec3::splat(Self::MIN_EXTENT))];
+        let dimensions = Self::clamp_dimensions(dimensions);
+        let cell_count = (dimensions.x * dimensions.y * dimensions.z) as usize;
+
+        Self {
+            bounds,
+            cell_size: (bounds[1] - bounds[0]) / dimensions.as_vec3(),
+            dimensions,
+            cells: vec![Vec::new(); cell_count],
+            len: 0,
+        }
+    }
+
+    /// The dimensions a grid built with `dimensions` has
+    pub fn clamp_dimensions(dimensions: [i32; 3]) -> IVec3 {
+        IVec3::from(dimensions).clamp(IVec3::ONE, IVec3::splat(Self::MAX_DIMENSION))
+    }
+
+    pub fn bounds(&self) -> [Vec3; 2] {
+        self.bounds
+    }
+
+    pub fn dimensions(&self) -> IVec3 {
+        self.dimensions
+    }
+
+    pub fn cell_size(&self) -> Vec3 {
+        self.cell_size
+    }
+
+    /// Number of entities in the grid
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Cell coordinate containing `pos`. Positions outside the bounds are clamped to the border cells.
+    pub fn cell_index(&self, pos: Vec3) -> IVec3 {
+        ((pos - self.bounds[0]) / self.cell_size)
+            .floor()
+            .as_ivec3()
+            .clamp(IVec3::ZERO, self.dimensions - IVec3::ONE)
+    }
+
+    /// Position of the cell at `index` in [`cells`](Self::cells).
+    pub fn flat_index(&self, index: IVec3) -> usize {
+        (index.x + self.dimensions.x * (index.y + self.dimensions.y * index.z)) as usize
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec3) {
+        let i = self.flat_index(self.cell_index(pos));
+        self.cells[i].push(entity);
+        self.len += 1;
+    }
+
+    /// Removes `entity` from the cell containing `pos`. Returns false if it was not there.
+    pub fn remove(&mut self, entity: Entity, pos: Vec3) -> bool {
+        let i = self.flat_index(self.cell_index(pos));
+        let cell = &mut self.cells[i];
+        match cell.iter().position(|e| *e == entity) {
+            Some(slot) => {
+                cell.swap_remove(slot);
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves `entity` from the cell containing `prev` to the one containing `new_pos`.
+    /// Does nothing if both positions fall in the same cell.
+    ///
+    /// `entity` must already be in the grid at `prev`, which only boids are. Debug builds panic
+    /// otherwise, and release builds insert it at `new_pos` so a stale `prev` never drops a boid.
+    pub fn move_entity(&mut self, entity: Entity, prev: Vec3, new_pos: Vec3) {
+        if self.cell_index(prev) == self.cell_index(new_pos) {
+            return;
+        }
+        let removed = self.remove(entity, prev);
+        debug_assert!(removed, "{:?} was not in the grid cell of {}", entity, prev);
+        self.insert(entity, new_pos);
+    }
+
+    /// Entities in the cell at `index`. Indices outside the grid yield an empty slice.
+    pub fn query_cell(&self, index: IVec3) -> &[Entity] {
+        if index.cmplt(IVec3::ZERO).any() || index.cmpge(self.dimensions).any() {
+            return &[];
+        }
+        &self.cells[self.flat_index(index)]
+    }
+
+    /// Every cell overlapping the cube of half-size `radius` around `pos`.
+    /// That is the 27 surrounding cells for radii up to one cell, and more for larger radii.
+    pub fn cells_in_radius(&self, pos: Vec3, radius: f32) -> impl Iterator<Item = &[Entity]> + '_ {
+        self.indices_in_radius(pos, radius).map(move |index| self.query_cell(index))
+    }
+
+    /// Coordinates of the cells [`cells_in_radius`](Self::cells_in_radius) visits, all inside the grid.
+    pub fn indices_in_radius(&self, pos: Vec3, radius: f32) -> impl Iterator<Item = IVec3> {
+        let min = self.cell_index(pos - Vec3::splat(radius));
+        let max = self.cell_index(pos + Vec3::splat(radius));
+
+        (min.z..=max.z).flat_map(move |z| {
+            (min.y..=max.y).flat_map(move |y| {
+                (min.x..=max.x).map(move |x| IVec3::new(x, y, z))
+            })
+        })
+    }
+
+    /// Coordinates of the cells within `radius` of the ray from `origin` along the normalized `direction`,
+    /// roughly in order of distance from the origin and each visited once. Empty if the ray misses the grid.
+    pub fn indices_along_ray(&self, origin: Vec3, direction: Vec3, radius: f32) -> Vec<IVec3> {
+        // Clip the ray to the bounds grown by the radius, with the slab method
+        let min = self.bounds[0] - Vec3::splat(radius);
+        let max = self.bounds[1] + Vec3::splat(radius);
+        let t1 = (min - origin) / direction;
+        let t2 = (max - origin) / direction;
+        let enter = t1.min(t2).max_element().max(0.);
+        let exit = t1.max(t2).min_element();
+        if enter > exit || exit.is_nan() {
+            return Vec::new();
+        }
+
+        // Half a cell per step, so no cell the ray passes through is skipped
+        let step = self.cell_size.min_element() * 0.5;
+        // Far enough from the origin, widening the bounds by `MIN_EXTENT` can round away to nothing
+        if step <= 0. {
+            return Vec::new();
+        }
+        let mut visited = vec![false; self.cells.len()];
+        let mut indices = Vec::new();
+        let mut t = enter;
+        while t <= exit + step {
+            for index in self.indices_in_radius(origin + direction * t.min(exit), radius) {
+                let flat = self.flat_index(index);
+                if !visited[flat] {
+                    visited[flat] = true;
+                    indices.push(index);
+                }
+            }
+            t += step;
+        }
+        indices
+    }
+
+    /// Every cell in the grid, in flat index order.
+    pub fn cells(&self) -> impl Iterator<Item = &[Entity]> + '_ {
+        self.cells.iter().map(|cell| cell.as_slice())
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> GridMap {
+        // 20 x 20 x 20 cells of size 1
+        GridMap::new([Vec3::ZERO, Vec3::splat(20.)], [20, 20, 20])
+    }
+
+    #[test]
+    fn mirrored_cells_do_not_share_a_slot() {
+        let grid = grid();
+        assert_ne!(grid.flat_index(IVec3::new(1, 11, 0)), grid.flat_index(IVec3::new(11, 1, 0)));
+
+        let mut grid = grid;
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+        grid.insert(a, Vec3::new(1.5, 11.5, 0.5));
+        grid.insert(b, Vec3::new(11.5, 1.5, 0.5));
+        assert_eq!(grid.query_cell(IVec3::new(1, 11, 0)), &[a]);
+        assert_eq!(grid.query_cell(IVec3::new(11, 1, 0)), &[b]);
+    }
+
+    #[test]
+    fn every_cell_has_its_own_slot() {
+        let grid = GridMap::new([Vec3::ZERO, Vec3::new(4., 3., 5.)], [4, 3, 5]);
+        let mut slots: Vec<usize> = grid.indices_in_radius(Vec3::splat(2.), 10.).map(|index| grid.flat_index(index)).collect();
+        slots.sort_unstable();
+        slots.dedup();
+        assert_eq!(slots, (0..60).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn insert_remove_and_move() {
+        let mut grid = grid();
+        let entity = Entity::from_raw(7);
+
+        grid.insert(entity, Vec3::new(2.5, 2.5, 2.5));
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid.query_cell(IVec3::splat(2)), &[entity]);
+
+        grid.move_entity(entity, Vec3::new(2.5, 2.5, 2.5), Vec3::new(2.9, 2.1, 2.2));
+        assert_eq!(grid.query_cell(IVec3::splat(2)), &[entity]);
+
+        grid.move_entity(entity, Vec3::new(2.9, 2.1, 2.2), Vec3::new(9.5, 3.5, 4.5));
+        assert!(grid.query_cell(IVec3::splat(2)).is_empty());
+        assert_eq!(grid.query_cell(IVec3::new(9, 3, 4)), &[entity]);
+        assert_eq!(grid.len(), 1);
+
+        assert!(!grid.remove(entity, Vec3::new(2.5, 2.5, 2.5)));
+        assert!(grid.remove(entity, Vec3::new(9.5, 3.5, 4.5)));
+        assert!(grid.is_empty());
+    }
+
+    #[test]
+    fn inverted_bounds_are_widened() {
+        let grid = GridMap::new([Vec3::new(0., 5., 0.), Vec3::new(10., 5., -3.)], [10, 10, 10]);
+        let [min, max] = grid.bounds();
+        assert!(max.cmpgt(min).all());
+        assert!(grid.cell_size().cmpgt(Vec3::ZERO).all());
+    }
+
+    #[test]
+    fn dimensions_are_clamped_without_overflowing() {
+        let grid = GridMap::new([Vec3::ZERO, Vec3::ONE], [0, -3, i32::MAX]);
+        assert_eq!(grid.dimensions(), IVec3::new(1, 1, GridMap::MAX_DIMENSION));
+        assert_eq!(grid.cells().count(), GridMap::MAX_DIMENSION as usize);
+    }
+
+    #[test]
+    fn positions_outside_are_clamped_to_the_border() {
+        let mut grid = grid();
+        assert_eq!(grid.cell_index(Vec3::new(-5., 25., 10.5)), IVec3::new(0, 19, 10));
+        assert_eq!(grid.cell_index(Vec3::splat(20.)), IVec3::splat(19));
+
+        let entity = Entity::from_raw(3);
+        grid.insert(entity, Vec3::splat(-100.));
+        assert_eq!(grid.query_cell(IVec3::ZERO), &[entity]);
+        assert!(grid.query_cell(IVec3::new(-1, 0, 0)).is_empty());
+        assert!(grid.query_cell(IVec3::new(0, 20, 0)).is_empty());
+    }
+
+    #[test]
+    fn rays_through_cells_without_size_end() {
+        let far = Vec3::splat(1e9);
+        let grid = GridMap::new([far, far], [4, 4, 4]);
+        assert_eq!(grid.cell_size(), Vec3::ZERO);
+
+        assert!(grid.indices_along_ray(Vec3::ZERO, Vec3::ONE.normalize(), 1.).is_empty());
+    }
+}
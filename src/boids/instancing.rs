@@ -0,0 +1,292 @@
+//! Draws every boid of a species with one instanced draw call of a single mesh,
+//! after bevy's `shader_instancing` example.
+
+use bevy::{
+    core_pipeline::core_3d::Opaque3d,
+    ecs::{query::QueryItem, system::{lifetimeless::*, SystemParamItem}},
+    pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+            SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        view::{ExtractedView, NoFrustumCulling},
+        RenderApp, RenderStage,
+    },
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::{animation, Boid, BoidSystem, Species, SpeciesTable, WingBeat};
+use crate::{GameState, loading::SceneAssets};
+
+pub(super) struct InstancedBirdsPlugin;
+
+impl Plugin for InstancedBirdsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(ExtractComponentPlugin::<BirdInstances>::default())
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_instance_batches))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(animation::start_wing_beats)
+                    .with_system(animation::beat_wings.after(BoidSystem::Integrate))
+                    .with_system(update_bird_instances.after(BoidSystem::Move).after(animation::beat_wings))
+            );
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawBirdsInstanced>()
+            .init_resource::<BirdInstancingPipeline>()
+            .init_resource::<SpecializedMeshPipelines<BirdInstancingPipeline>>()
+            .add_system_to_stage(RenderStage::Queue, queue_bird_instances)
+            .add_system_to_stage(RenderStage::Prepare, prepare_instance_buffers);
+    }
+}
+
+/// Per-instance vertex data, matching the instance attributes of `bird_instancing.wgsl`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct BirdInstance {
+    position: Vec3,
+    scale: f32,
+    rotation: [f32; 4],
+    color: [f32; 4],
+    /// [`WingBeat::angle`]
+    wing_angle: f32,
+}
+
+/// The instances drawn with this entity's mesh, refilled from the boids of `species` every frame.
+#[derive(Component, Clone)]
+struct BirdInstances {
+    species: Species,
+    instances: Vec<BirdInstance>,
+}
+
+impl ExtractComponent for BirdInstances {
+    type Query = &'static BirdInstances;
+    type Filter = ();
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Self {
+        item.clone()
+    }
+}
+
+/// One mesh entity per species, carrying the instances of every boid of that species.
+fn spawn_instance_batches (
+    mut commands: Commands,
+    scenes: Res<SceneAssets>,
+) {
+    for species in Species::ALL {
+        let mesh = match species {
+            Species::Starling | Species::Gull => scenes.bird_mesh.clone(),
+            Species::Pigeon => scenes.pigeon_mesh.clone(),
+        };
+
+        commands.spawn((
+            mesh,
+            SpatialBundle::VISIBLE_IDENTITY,
+            BirdInstances { species, instances: Vec::new() },
+            // The instances are spread over the whole flock, far outside the mesh's own bounds
+            NoFrustumCulling,
+            Name::new(format!("{:?} instances", species)),
+        ));
+    }
+}
+
+fn update_bird_instances (
+    mut q_batches: Query<&mut BirdInstances>,
+    q_boids: Query<(&Transform, &Species, Option<&WingBeat>), With<Boid>>,
+    table: Res<SpeciesTable>,
+) {
+    for mut batch in q_batches.iter_mut() {
+        let species = batch.species;
+        let params = table.params(species);
+        let color = params.color.as_linear_rgba_f32();
+
+        batch.instances.clear();
+        batch.instances.extend(
+            q_boids.iter()
+                .filter(|(_, boid_species, _)| **boid_species == species)
+                .map(|(trans, _, wings)| BirdInstance {
+                    position: trans.translation,
+                    scale: params.scale,
+                    rotation: trans.rotation.to_array(),
+                    color,
+                    // Spawned this frame
+                    wing_angle: wings.map_or(0., WingBeat::angle),
+                })
+        );
+    }
+}
+
+#[derive(Component)]
+struct InstanceBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+fn prepare_instance_buffers (
+    mut commands: Commands,
+    query: Query<(Entity, &BirdInstances)>,
+    render_device: Res<RenderDevice>,
+) {
+    for (entity, batch) in query.iter() {
+        if batch.instances.is_empty() {
+            continue;
+        }
+
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("bird instance buffer"),
+            contents: bytemuck::cast_slice(batch.instances.as_slice()),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        commands.entity(entity).insert(InstanceBuffer { buffer, length: batch.instances.len() });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_bird_instances (
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    instancing_pipeline: Res<BirdInstancingPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<BirdInstancingPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    q_batches: Query<(Entity, &MeshUniform, &Handle<Mesh>), With<BirdInstances>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Opaque3d>)>,
+) {
+    let draw_birds = opaque_3d_draw_functions.read().get_id::<DrawBirdsInstanced>().unwrap();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples);
+
+    for (view, mut opaque_phase) in views.iter_mut() {
+        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+
+        for (entity, mesh_uniform, mesh_handle) in q_batches.iter() {
+            if let Some(mesh) = meshes.get(mesh_handle) {
+                let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                let pipeline = match pipelines.specialize(&mut pipeline_cache, &instancing_pipeline, key, &mesh.layout) {
+                    Ok(pipeline) => pipeline,
+                    Err(err) => {
+                        error!("{}", err);
+                        continue;
+                    }
+                };
+
+                opaque_phase.add(Opaque3d {
+                    entity,
+                    pipeline,
+                    draw_function: draw_birds,
+                    distance: rangefinder.distance(&mesh_uniform.transform),
+                });
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+struct BirdInstancingPipeline {
+    shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for BirdInstancingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let shader = world.resource::<AssetServer>().load("shaders/bird_instancing.wgsl");
+        let mesh_pipeline = world.resource::<MeshPipeline>().clone();
+
+        Self { shader, mesh_pipeline }
+    }
+}
+
+impl SpecializedMeshPipeline for BirdInstancingPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+        // The mesh pipeline uses locations 0 to 6 for the mesh attributes
+        descriptor.vertex.shader = self.shader.clone();
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<BirdInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                // Position and scale
+                VertexAttribute { format: VertexFormat::Float32x4, offset: 0, shader_location: 7 },
+                // Rotation
+                VertexAttribute { format: VertexFormat::Float32x4, offset: 16, shader_location: 8 },
+                // Colour
+                VertexAttribute { format: VertexFormat::Float32x4, offset: 32, shader_location: 9 },
+                // Wing angle
+                VertexAttribute { format: VertexFormat::Float32, offset: 48, shader_location: 10 },
+            ],
+        });
+        descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
+
+        // The birds are drawn in their bind pose with the wings swung by the shader, so the skinned mesh layout is never needed
+        descriptor.layout = Some(vec![
+            self.mesh_pipeline.view_layout.clone(),
+            self.mesh_pipeline.mesh_layout.clone(),
+        ]);
+
+        Ok(descriptor)
+    }
+}
+
+type DrawBirdsInstanced = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawMeshInstanced,
+);
+
+struct DrawMeshInstanced;
+
+impl EntityRenderCommand for DrawMeshInstanced {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SQuery<Read<Handle<Mesh>>>,
+        SQuery<Read<InstanceBuffer>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, mesh_query, instance_buffer_query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let mesh_handle = mesh_query.get(item).unwrap();
+        let instance_buffer = match instance_buffer_query.get_inner(item) {
+            Ok(instance_buffer) => instance_buffer,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        let gpu_mesh = match meshes.into_inner().get(mesh_handle) {
+            Some(gpu_mesh) => gpu_mesh,
+            None => return RenderCommandResult::Failure,
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed { buffer, index_format, count } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..instance_buffer.length as u32);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, 0..instance_buffer.length as u32);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
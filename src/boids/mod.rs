@@ -0,0 +1,679 @@
+use bevy::{prelude::*, math::vec3, utils::HashMap};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::{GameState, actions::{self, Actions}, loading::SceneAssets};
+
+mod animation;
+mod boundary;
+mod control;
+mod flight;
+mod grid;
+mod instancing;
+mod neighbours;
+mod obstacle;
+mod predator;
+mod savestate;
+mod settings;
+mod simulation;
+mod snapshot;
+mod species;
+
+pub use animation::{FlightAnimation, WingBeat};
+pub use boundary::BoundaryMode;
+pub use control::{FlockControl, ResizeFlock};
+pub use flight::{bank_into_turn, facing, fly, SpeedRange, GRAVITY};
+pub use grid::GridMap;
+pub use neighbours::{neighbours, Neighbour, Perception};
+pub use obstacle::{Obstacle, ObstacleHit};
+pub use predator::Predator;
+pub use savestate::{FlockSave, LoadFlock, SaveFlock, SavedBoid, SavedPredator};
+pub use settings::BoidSettings;
+pub use simulation::{SimulationClock, SimulationRng, SimulationStep, SimulationSteps};
+pub use snapshot::{BoidSample, FlockSnapshot};
+pub use species::{Species, SpeciesParams, SpeciesRelation, SpeciesTable};
+
+/// The flock simulation together with the bird models.
+/// Insert a [`BirdRendering`] before adding the plugin to choose how the boids are drawn.
+pub struct BoidsPlugin;
+
+impl Plugin for BoidsPlugin {
+    fn build(&self, app: &mut App) {
+        let rendering = *app.world.get_resource_or_insert_with(BirdRendering::default);
+
+        app
+            .add_plugin(BoidSimulationPlugin)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(attach_predator_scenes)
+                    .with_system(tint_predator_materials)
+                    .with_system(save_state_hotkeys)
+                    .with_system(apply_flock_actions.after(actions::set_actions).before(BoidSystem::Prepare))
+            );
+
+        match rendering {
+            BirdRendering::Scenes => {
+                app.add_system_set(
+                    SystemSet::on_update(GameState::Playing)
+                        .with_system(attach_bird_scenes)
+                        .with_system(animation::start_flight_animations)
+                        .with_system(animation::drive_flap_speed.after(BoidSystem::Integrate))
+                );
+            }
+            BirdRendering::Instanced => {
+                app.add_plugin(instancing::InstancedBirdsPlugin);
+            }
+        }
+    }
+}
+
+/// How the boids are drawn, chosen at startup.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BirdRendering {
+    /// A glTF scene per boid, with the full node hierarchy and flight animation. Detailed, but slow for large flocks
+    Scenes,
+    /// One instanced draw call per species, with a single mesh in its bind pose whose wings are swung
+    /// up and down in the vertex shader instead of playing the flight clip
+    #[default]
+    Instanced,
+}
+
+/// The flock simulation only. Needs no window, renderer, assets or audio, so it also runs headless.
+pub struct BoidSimulationPlugin;
+
+impl Plugin for BoidSimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<BoidSettings>()
+            .init_resource::<SteeringWeights>()
+            .init_resource::<SpeciesTable>()
+            .init_resource::<SimulationStep>()
+            .init_resource::<SimulationClock>()
+            .init_resource::<SimulationRng>()
+            .init_resource::<FlockSnapshot>()
+            .init_resource::<FlockControl>()
+            .add_event::<SaveFlock>()
+            .add_event::<LoadFlock>()
+            .add_event::<ResizeFlock>()
+            .add_startup_system(init_grid_map)
+            .add_system_to_stage(CoreStage::PreUpdate, savestate::handle_save_state_events)
+            // Boids are spawned and despawned before the step, so their commands are applied
+            // by the time the rules run and no despawned boid is moved out of the grid
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::new()
+                    .with_run_criteria(simulation::playing)
+                    .with_system(control::resize_flock)
+                    .with_system(apply_settings_changes.after(control::resize_flock))
+            )
+            // Every step runs Prepare to Move, as often per frame as the SimulationClock says
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(simulation::run_simulation_steps.label(SimulationSteps))
+                    .label(BoidSystem::Prepare)
+                    .with_system(simulation::advance_simulation_step)
+                    .with_system(predator::sync_predator_count.after(simulation::advance_simulation_step))
+                    .with_system(snapshot::take_flock_snapshot.after(simulation::advance_simulation_step))
+                    .with_system(clear_steering.after(simulation::advance_simulation_step))
+            )
+            // The rules run in a fixed order so the summed force is bit-identical between runs.
+            // Within a rule each boid only writes its own force, so boids are steered in parallel.
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(SimulationSteps)
+                    .label(BoidSystem::Steering)
+                    .after(BoidSystem::Prepare)
+                    .with_system(avoid_nearby)
+                    .with_system(steer_towards_average_local_velocity.after(avoid_nearby))
+                    .with_system(steer_towards_local_center.after(steer_towards_average_local_velocity))
+                    .with_system(boundary::steer_inside_bounds.after(steer_towards_local_center))
+                    .with_system(steer_horizontal.after(boundary::steer_inside_bounds))
+                    .with_system(predator::flee_predators.after(steer_horizontal))
+                    .with_system(obstacle::avoid_obstacles.after(predator::flee_predators))
+                    .with_system(control::follow_flock_control.after(obstacle::avoid_obstacles))
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(SimulationSteps)
+                    .label(BoidSystem::Integrate)
+                    .after(BoidSystem::Steering)
+                    .with_system(apply_steering)
+                    .with_system(predator::hunt)
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(SimulationSteps)
+                    .label(BoidSystem::Move)
+                    .after(BoidSystem::Integrate)
+                    .with_system(move_boids)
+                    .with_system(predator::move_predators)
+                    .with_system(boundary::confine_to_bounds.after(move_boids).after(predator::move_predators))
+                    .with_system(obstacle::enforce_floors.after(boundary::confine_to_bounds))
+            )
+            .register_type::<Velocity>()
+            .register_type::<TargetVelocity>()
+            .register_type::<SteeringForce>()
+            .register_type::<SteeringWeights>()
+            .register_type::<BoidSettings>()
+            .register_type::<BoundaryMode>()
+            .register_type::<Species>()
+            .register_type::<SpeciesTable>()
+            ;
+    }
+}
+
+#[derive(SystemLabel, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BoidSystem {
+    /// Advances the [`SimulationStep`] and takes the [`FlockSnapshot`]
+    Prepare,
+    /// Rules that add to each boid's [`SteeringForce`]
+    Steering,
+    /// Turns the accumulated steering into a new [`Velocity`]
+    Integrate,
+    /// Moves boids along their velocity and updates the [`GridMap`]
+    Move,
+}
+
+/// Boids handed to each task by the parallel steering rules.
+const STEERING_BATCH_SIZE: usize = 256;
+
+/// Weight of each steering rule when the forces are summed.
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Copy, Debug)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct SteeringWeights {
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub bounds: f32,
+    pub level_flight: f32,
+    pub flee: f32,
+    pub obstacle: f32,
+    /// Away from the local centre while [`FlockControl::scatter`] lasts
+    pub scatter: f32,
+    /// Towards [`FlockControl::attractor`]
+    pub attract: f32,
+}
+
+impl SteeringWeights {
+    /// The same weight for every rule
+    pub fn splat(weight: f32) -> Self {
+        Self {
+            separation: weight,
+            alignment: weight,
+            cohesion: weight,
+            bounds: weight,
+            level_flight: weight,
+            flee: weight,
+            obstacle: weight,
+            scatter: weight,
+            attract: weight,
+        }
+    }
+}
+
+impl Default for SteeringWeights {
+    fn default() -> Self {
+        Self {
+            separation: 1.5,
+            alignment: 1.0,
+            cohesion: 0.6,
+            bounds: 1.0,
+            level_flight: 0.5,
+            flee: 4.0,
+            obstacle: 3.0,
+            scatter: 3.0,
+            attract: 1.5,
+        }
+    }
+}
+
+/// The rules that add to a boid's [`SteeringForce`], one per weight in [`SteeringWeights`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SteeringRule {
+    Separation,
+    Alignment,
+    Cohesion,
+    Bounds,
+    LevelFlight,
+    Flee,
+    Obstacle,
+    Scatter,
+    Attract,
+}
+
+impl SteeringRule {
+    pub const COUNT: usize = 9;
+
+    pub const ALL: [SteeringRule; Self::COUNT] = [
+        SteeringRule::Separation,
+        SteeringRule::Alignment,
+        SteeringRule::Cohesion,
+        SteeringRule::Bounds,
+        SteeringRule::LevelFlight,
+        SteeringRule::Flee,
+        SteeringRule::Obstacle,
+        SteeringRule::Scatter,
+        SteeringRule::Attract,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Bundle)]
+struct BoidBundle {
+    boid: Boid,
+    species: Species,
+    velocity: Velocity,
+    target: TargetVelocity,
+    steering: SteeringForce,
+    transform: Transform,
+    global_transform: GlobalTransform,
+}
+
+#[derive(Component)]
+pub struct Boid;
+
+#[derive(Component, Debug, Reflect)]
+pub struct Velocity(pub Vec3);
+
+/// The velocity the steering rules asked for this frame, before `max_force` and the time step were applied.
+#[derive(Component, Debug, Reflect)]
+pub struct TargetVelocity(pub Vec3);
+
+/// Accumulates the weighted steering forces of every rule until [`BoidSystem::Integrate`].
+/// They are kept until the next step's [`BoidSystem::Prepare`], so the forces that moved a boid can be inspected.
+#[derive(Component, Default, Debug, Reflect)]
+pub struct SteeringForce {
+    pub total: Vec3,
+    /// What each rule added to `total`, indexed by [`SteeringRule::index`]
+    pub contributions: [Vec3; SteeringRule::COUNT],
+}
+
+impl SteeringForce {
+    pub fn add(&mut self, rule: SteeringRule, weight: f32, force: Vec3) {
+        let weighted = weight * force;
+        self.total += weighted;
+        self.contributions[rule.index()] += weighted;
+    }
+
+    pub fn contribution(&self, rule: SteeringRule) -> Vec3 {
+        self.contributions[rule.index()]
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+fn spawn_flock (
+    commands: &mut Commands,
+    grid_map: &mut GridMap,
+    rng: &mut SimulationRng,
+    settings: &BoidSettings,
+    table: &SpeciesTable,
+) {
+    let counts = table.split(settings.bird_count);
+
+    for species in Species::ALL.into_iter().flat_map(|species| std::iter::repeat(species).take(counts[species.index()] as usize)) {
+        spawn_boid(commands, grid_map, rng, settings, table, species);
+    }
+}
+
+/// Spawns one boid of `species` at a random point inside the bounds.
+fn spawn_boid (
+    commands: &mut Commands,
+    grid_map: &mut GridMap,
+    rng: &mut SimulationRng,
+    settings: &BoidSettings,
+    table: &SpeciesTable,
+    species: Species,
+) {
+    let rng = &mut rng.0;
+    let bounds = settings.bounds;
+
+    let speed = settings.speed * table.params(species).speed_factor;
+    let pos = Vec3::new(rng.gen_range(bounds[0].x..bounds[1].x), rng.gen_range(bounds[0].y..bounds[1].y), rng.gen_range(bounds[0].z..bounds[1].z));
+
+    let vel = Vec3::new(rng.gen_range(-10..10) as f32, rng.gen_range(-3..3) as f32, rng.gen_range(-10..10) as f32).normalize_or_zero() * speed;
+
+    let id = commands.spawn((
+        BoidBundle {
+            boid: Boid,
+            species,
+            velocity: Velocity(vel),
+            target: TargetVelocity(vel),
+            steering: SteeringForce::default(),
+            transform: Transform::from_translation(pos).with_rotation(facing(vel)),
+            global_transform: GlobalTransform::default(),
+        },
+        Name::new("Boid"),
+    )).id();
+
+    grid_map.insert(id, pos);
+}
+
+/// Applies edits to the flock size and grid layout, and spawns the first flock.
+///
+/// Rebuilds the grid when its bounds or dimensions change, and respawns the flock when `bird_count`
+/// differs from the flock size. The flock size is read from the grid, which is updated immediately,
+/// rather than from the boid entities, whose spawning is deferred.
+fn apply_settings_changes (
+    mut commands: Commands,
+    mut grid: ResMut<GridMap>,
+    mut rng: ResMut<SimulationRng>,
+    mut step: ResMut<SimulationStep>,
+    mut settings: ResMut<BoidSettings>,
+    species: Res<SpeciesTable>,
+    q_boids: Query<(Entity, &Transform), With<Boid>>,
+) {
+    // Boids and predators spawn at random points inside the bounds, which must not be empty on any axis
+    if (settings.bounds[1] - settings.bounds[0]).cmplt(Vec3::splat(GridMap::MIN_EXTENT)).any() {
+        warn!("Ignoring bounds {:?}, they must be at least {} wide on every axis", settings.bounds, GridMap::MIN_EXTENT);
+        settings.bounds = grid.bounds();
+    }
+    // The grid would clamp them, and then never match the settings again
+    if GridMap::clamp_dimensions(settings.dimensions) != IVec3::from(settings.dimensions) {
+        warn!("Ignoring grid dimensions {:?}, they must be between 1 and {} on every axis", settings.dimensions, GridMap::MAX_DIMENSION);
+        settings.dimensions = grid.dimensions().into();
+    }
+
+    let respawn = grid.len() != settings.bird_count as usize;
+    let resized = grid.bounds() != settings.bounds || grid.dimensions() != IVec3::from(settings.dimensions);
+
+    if resized {
+        *grid = GridMap::new(settings.bounds, settings.dimensions);
+        if !respawn {
+            for (entity, trans) in q_boids.iter() {
+                grid.insert(entity, trans.translation);
+            }
+        }
+    }
+
+    if respawn {
+        for (entity, _) in q_boids.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        grid.clear();
+        // Start over from the seed so a respawned flock is reproducible too
+        *rng = SimulationRng::from_settings(&settings);
+        step.count = 0;
+        spawn_flock(&mut commands, &mut grid, &mut rng, &settings, &species);
+    }
+}
+
+/// Gives newly spawned boids the model of their species.
+fn attach_bird_scenes (
+    mut commands: Commands,
+    mut q_new: Query<(Entity, &Species, &mut Transform), (With<Boid>, Without<Handle<Scene>>)>,
+    scenes: Res<SceneAssets>,
+    table: Res<SpeciesTable>,
+) {
+    for (entity, species, mut transform) in q_new.iter_mut() {
+        let scene = match species {
+            Species::Starling | Species::Gull => scenes.bird.clone(),
+            Species::Pigeon => scenes.pigeon.clone(),
+        };
+        transform.scale = Vec3::splat(table.params(*species).scale);
+        commands.entity(entity).insert((scene, VisibilityBundle::default()));
+    }
+}
+
+/// Gives newly spawned predators the hawk model.
+fn attach_predator_scenes (
+    mut commands: Commands,
+    mut q_new: Query<(Entity, &mut Transform), (With<Predator>, Without<Handle<Scene>>)>,
+    scenes: Res<SceneAssets>,
+) {
+    for (entity, mut transform) in q_new.iter_mut() {
+        transform.scale = Vec3::splat(0.12);
+        commands.entity(entity).insert((scenes.hawk.clone(), VisibilityBundle::default()));
+    }
+}
+
+/// Multiplied into the colours of the hawk model, which is the pigeon's model scaled up
+const PREDATOR_TINT: [f32; 3] = [0.6, 0.38, 0.22];
+
+/// Swaps the materials that appear inside a predator's scene for tinted copies, made once per material.
+fn tint_predator_materials (
+    mut q_materials: Query<(Entity, &mut Handle<StandardMaterial>), Added<Handle<StandardMaterial>>>,
+    q_parents: Query<&Parent>,
+    q_predators: Query<(), With<Predator>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut tinted: Local<HashMap<Handle<StandardMaterial>, Handle<StandardMaterial>>>,
+) {
+    for (entity, mut material) in q_materials.iter_mut() {
+        // The mesh sits somewhere below the predator in the scene hierarchy
+        let in_predator = std::iter::successors(Some(entity), |ancestor| q_parents.get(*ancestor).ok().map(Parent::get))
+            .any(|ancestor| q_predators.contains(ancestor));
+        if !in_predator {
+            continue;
+        }
+
+        if !tinted.contains_key(&*material) {
+            let mut copy = match materials.get(&*material) {
+                Some(original) => original.clone(),
+                None => continue,
+            };
+            copy.base_color = copy.base_color * PREDATOR_TINT;
+            tinted.insert(material.clone(), materials.add(copy));
+        }
+        let copy = tinted[&*material].clone();
+        *material = copy;
+    }
+}
+
+fn move_boids (
+    mut boid_query: Query<(&mut Transform, Entity, &Velocity, &TargetVelocity), With<Boid>>,
+    mut grid: ResMut<GridMap>,
+    settings: Res<BoidSettings>,
+    step: Res<SimulationStep>,
+) {
+    for (mut transform, entity, velocity, target) in boid_query.iter_mut() {
+        let prev_pos = transform.translation;
+        transform.rotation = bank_into_turn(transform.rotation, velocity.0, target.0 - velocity.0, &settings, step.delta);
+
+        let new_pos = transform.translation + velocity.0 * step.delta;
+
+        transform.translation = new_pos;
+
+        grid.move_entity(entity, prev_pos, new_pos);
+    }
+}
+
+/// Seconds a scatter lasts
+const SCATTER_TIME: f32 = 1.5;
+/// Boids added or removed per press of the resize keys
+const RESIZE_STEP: i32 = 100;
+
+/// Passes the player's scatter, attract and resize actions on to the simulation.
+fn apply_flock_actions (
+    actions: Res<Actions>,
+    mut control: ResMut<FlockControl>,
+    mut resize_events: EventWriter<ResizeFlock>,
+) {
+    if actions.scatter {
+        control.scatter = SCATTER_TIME;
+    }
+    control.attractor = actions.attract;
+    if actions.resize_flock != 0 {
+        resize_events.send(ResizeFlock(actions.resize_flock * RESIZE_STEP));
+    }
+}
+
+/// File the save and load hotkeys use
+const SAVE_FILE: &str = "flock.ron";
+
+fn save_state_hotkeys (
+    actions: Res<Actions>,
+    mut save_events: EventWriter<SaveFlock>,
+    mut load_events: EventWriter<LoadFlock>,
+) {
+    if actions.save_flock {
+        save_events.send(SaveFlock(SAVE_FILE.into()));
+    }
+    if actions.load_flock {
+        load_events.send(LoadFlock(SAVE_FILE.into()));
+    }
+}
+
+fn init_grid_map (
+    mut commands: Commands,
+    settings: Res<BoidSettings>,
+) {
+    commands.insert_resource(GridMap::new(settings.bounds, settings.dimensions));
+}
+
+/// Reynolds steering: the change in velocity needed to fly along `direction` at `max_speed`.
+fn steer_towards(velocity: Vec3, direction: Vec3, max_speed: f32) -> Vec3 {
+    match direction.try_normalize() {
+        Some(dir) => dir * max_speed - velocity,
+        None => Vec3::ZERO,
+    }
+}
+
+/// Separation: steer away from boids of any species that are too close,
+/// and from species this one avoids as soon as they are perceived.
+fn avoid_nearby (
+    mut query: Query<(Entity, &Transform, &Velocity, &Species, &mut SteeringForce)>,
+    snapshot: Res<FlockSnapshot>,
+    grid: Res<GridMap>,
+    settings: Res<BoidSettings>,
+    weights: Res<SteeringWeights>,
+    table: Res<SpeciesTable>,
+) {
+    let separation = settings.separation_distance;
+
+    query.par_for_each_mut(STEERING_BATCH_SIZE, |(entity, trans, vel, species, mut steering)| {
+        let params = table.params(*species);
+        let avoid_radius = (settings.perception_radius * params.perception_factor).max(separation);
+        let radius = if table.avoids_any(*species) { avoid_radius } else { separation };
+
+        let mut avoidance_vec: Vec3 = Vec3::ZERO;
+
+        for (near, other) in snapshot.neighbours(&grid, entity, trans.translation, vel.0, Perception::all_around(radius)) {
+            let reach = if table.relation(*species, other.species) == SpeciesRelation::Avoid { avoid_radius } else { separation };
+            if near.distance < reach {
+                avoidance_vec += -near.offset.normalize_or_zero() * (reach - near.distance);
+            }
+        }
+
+        let weight = weights.separation * params.weights.separation;
+        steering.add(SteeringRule::Separation, weight, steer_towards(vel.0, avoidance_vec, settings.max_speed * params.speed_factor));
+    });
+}
+
+/// Alignment: steer towards the average heading of the perceived flockmates.
+fn steer_towards_average_local_velocity (
+    mut query: Query<(Entity, &Transform, &Velocity, &Species, &mut SteeringForce)>,
+    snapshot: Res<FlockSnapshot>,
+    grid: Res<GridMap>,
+    settings: Res<BoidSettings>,
+    weights: Res<SteeringWeights>,
+    table: Res<SpeciesTable>,
+) {
+    query.par_for_each_mut(STEERING_BATCH_SIZE, |(entity, trans, vel, species, mut steering)| {
+        let params = table.params(*species);
+        let perception = settings.perception_for(params);
+
+        let mut sum_v = Vec3::ZERO;
+        let mut count = 0;
+
+        for (_, other) in snapshot.neighbours(&grid, entity, trans.translation, vel.0, perception) {
+            if table.relation(*species, other.species) == SpeciesRelation::Flock {
+                sum_v += other.velocity;
+                count += 1;
+            }
+        }
+
+        // Skip if no neighbours
+        if count == 0 { return; }
+
+        let average_v = sum_v / count as f32;
+        let weight = weights.alignment * params.weights.alignment;
+        steering.add(SteeringRule::Alignment, weight, steer_towards(vel.0, average_v, settings.max_speed * params.speed_factor));
+    });
+}
+
+/// Cohesion: steer towards the centre of mass of the perceived flockmates.
+fn steer_towards_local_center (
+    mut query: Query<(Entity, &Transform, &Velocity, &Species, &mut SteeringForce)>,
+    snapshot: Res<FlockSnapshot>,
+    grid: Res<GridMap>,
+    settings: Res<BoidSettings>,
+    weights: Res<SteeringWeights>,
+    table: Res<SpeciesTable>,
+) {
+    query.par_for_each_mut(STEERING_BATCH_SIZE, |(entity, trans, vel, species, mut steering)| {
+        let params = table.params(*species);
+        let perception = settings.perception_for(params);
+
+        let mut sum_offset = Vec3::ZERO;
+        let mut count = 0;
+
+        for (near, other) in snapshot.neighbours(&grid, entity, trans.translation, vel.0, perception) {
+            if table.relation(*species, other.species) == SpeciesRelation::Flock {
+                sum_offset += near.offset;
+                count += 1;
+            }
+        }
+
+        // Skip if no neighbours
+        if count == 0 { return; }
+
+        let weight = weights.cohesion * params.weights.cohesion;
+        steering.add(SteeringRule::Cohesion, weight, steer_towards(vel.0, sum_offset / count as f32, settings.max_speed * params.speed_factor));
+    });
+}
+
+/// Keeps climbs and dives shallow by pushing the vertical speed back within 10% of the speed.
+fn steer_horizontal (
+    mut query: Query<(&Velocity, &Species, &mut SteeringForce)>,
+    weights: Res<SteeringWeights>,
+    table: Res<SpeciesTable>,
+) {
+    for (vel, species, mut steering) in query.iter_mut() {
+        let max_climb = 0.1 * vel.0.length();
+        let climb_correction = vel.0.y.clamp(-max_climb, max_climb) - vel.0.y;
+        steering.add(SteeringRule::LevelFlight, weights.level_flight * table.params(*species).weights.level_flight, vec3(0., climb_correction, 0.));
+    }
+}
+
+/// Starts the step with no steering. Done here rather than after [`BoidSystem::Integrate`]
+/// so the forces of the last step stay readable until the next one.
+fn clear_steering (
+    mut query: Query<&mut SteeringForce>,
+) {
+    for mut steering in query.iter_mut() {
+        steering.clear();
+    }
+}
+
+/// Combines the accumulated steering into a new velocity, limited by `max_force` and the speed range.
+/// With `flight_physics` the speed also responds to thrust, gravity and drag, see [`fly`].
+fn apply_steering (
+    mut query: Query<(&Species, &SteeringForce, &mut TargetVelocity, &mut Velocity)>,
+    settings: Res<BoidSettings>,
+    table: Res<SpeciesTable>,
+    step: Res<SimulationStep>,
+) {
+    for (species, steering, mut target, mut vel) in query.iter_mut() {
+        let speed_factor = table.params(*species).speed_factor;
+        let speeds = SpeedRange {
+            cruise: settings.speed * speed_factor,
+            min: settings.min_speed * speed_factor,
+            max: settings.max_speed * speed_factor,
+        };
+        let force = (steering.total * settings.steering_factor).clamp_length_max(settings.max_force);
+        target.0 = vel.0 + force;
+
+        if settings.flight_physics {
+            vel.0 = fly(vel.0, force, speeds, &settings, step.delta);
+        } else {
+            let new_vel = vel.0 + force * step.delta;
+            let speed = new_vel.length().clamp(speeds.min, speeds.max.max(speeds.min));
+            vel.0 = new_vel.try_normalize().unwrap_or_else(|| vel.0.normalize_or_zero()) * speed;
+        }
+    }
+}
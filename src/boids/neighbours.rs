@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+use super::GridMap;
+
+/// How far, and in which directions, a boid can see its flockmates.
+#[derive(Clone, Copy, Debug)]
+pub struct Perception {
+    pub radius: f32,
+    /// Full opening angle of the view cone in radians. `None` sees all around.
+    pub field_of_view: Option<f32>,
+}
+
+impl Perception {
+    pub fn all_around(radius: f32) -> Self {
+        Self { radius, field_of_view: None }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Neighbour {
+    pub entity: Entity,
+    /// Vector from the observing boid to this neighbour.
+    pub offset: Vec3,
+    pub distance: f32,
+}
+
+/// The radius and view cone of a [`Perception`], prepared for testing many offsets from one boid.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct View {
+    radius_sqrd: f32,
+    min_cos: Option<f32>,
+    forward: Vec3,
+}
+
+impl View {
+    pub(super) fn new(perception: Perception, forward: Vec3) -> Self {
+        Self {
+            radius_sqrd: perception.radius * perception.radius,
+            min_cos: perception.field_of_view.map(|fov| (fov * 0.5).cos()),
+            forward: forward.normalize_or_zero(),
+        }
+    }
+
+    /// Distance to a flockmate at `offset`, if it can be seen.
+    pub(super) fn sees(&self, offset: Vec3) -> Option<f32> {
+        let dist_sqrd = offset.length_squared();
+        if dist_sqrd > self.radius_sqrd {
+            return None;
+        }
+
+        let distance = dist_sqrd.sqrt();
+        if let Some(min_cos) = self.min_cos {
+            if distance > 0. && self.forward != Vec3::ZERO && self.forward.dot(offset / distance) < min_cos {
+                return None;
+            }
+        }
+        Some(distance)
+    }
+}
+
+/// Flockmates of `this` that lie within the perception radius and view cone.
+///
+/// Scans every grid cell the perception sphere overlaps, so boids on a cell boundary still
+/// see neighbours on the other side. `position` looks up the current position of an entity.
+pub fn neighbours<'a, F>(
+    grid: &'a GridMap,
+    this: Entity,
+    pos: Vec3,
+    forward: Vec3,
+    perception: Perception,
+    position: F,
+) -> impl Iterator<Item = Neighbour> + 'a
+where
+    F: Fn(Entity) -> Option<Vec3> + 'a,
+{
+    let view = View::new(perception, forward);
+
+    grid.cells_in_radius(pos, perception.radius)
+        .flat_map(|cell| cell.iter().copied())
+        .filter(move |entity| *entity != this)
+        .filter_map(move |entity| {
+            let offset = position(entity)? - pos;
+            let distance = view.sees(offset)?;
+            Some(Neighbour { entity, offset, distance })
+        })
+}
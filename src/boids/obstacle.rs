@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+
+use super::{steer_towards, Boid, BoidSettings, GridMap, Species, SpeciesTable, SteeringForce, SteeringRule, SteeringWeights, Velocity};
+
+/// Scene geometry that boids steer around. The shape is centred on the entity's translation
+/// and, for boxes, aligned with the world axes.
+#[derive(Component, Clone, Copy, Debug)]
+pub enum Obstacle {
+    Sphere { radius: f32 },
+    Aabb { half_extents: Vec3 },
+    /// Solid half-space behind the plane through `translation + normal * offset`.
+    /// Nothing can pass through it, so it also works as a hard floor.
+    Plane { normal: Vec3, offset: f32 },
+}
+
+/// Where a ray first meets an obstacle.
+#[derive(Clone, Copy, Debug)]
+pub struct ObstacleHit {
+    pub distance: f32,
+    /// Surface normal at the hit point
+    pub normal: Vec3,
+}
+
+impl Obstacle {
+    /// Casts a ray from `origin` along the unit vector `dir` against the shape grown by `margin`.
+    pub fn raycast(&self, center: Vec3, origin: Vec3, dir: Vec3, margin: f32) -> Option<ObstacleHit> {
+        match *self {
+            Obstacle::Sphere { radius } => {
+                let radius = radius + margin;
+                let to_origin = origin - center;
+                if to_origin.length_squared() <= radius * radius {
+                    return Some(ObstacleHit { distance: 0., normal: to_origin.normalize_or_zero() });
+                }
+
+                let b = to_origin.dot(dir);
+                let c = to_origin.length_squared() - radius * radius;
+                let discriminant = b * b - c;
+                if b > 0. || discriminant < 0. {
+                    return None;
+                }
+
+                let distance = -b - discriminant.sqrt();
+                let normal = (origin + dir * distance - center).normalize_or_zero();
+                Some(ObstacleHit { distance, normal })
+            }
+            Obstacle::Aabb { half_extents } => {
+                let min = center - half_extents - Vec3::splat(margin);
+                let max = center + half_extents + Vec3::splat(margin);
+
+                // Slab test, remembering which axis the ray enters through
+                let mut t_enter = f32::NEG_INFINITY;
+                let mut t_exit = f32::INFINITY;
+                let mut normal = Vec3::ZERO;
+                for axis in 0..3 {
+                    if dir[axis].abs() < f32::EPSILON {
+                        if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                            return None;
+                        }
+                        continue;
+                    }
+
+                    let t0 = (min[axis] - origin[axis]) / dir[axis];
+                    let t1 = (max[axis] - origin[axis]) / dir[axis];
+                    let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+                    if near > t_enter {
+                        t_enter = near;
+                        normal = Vec3::ZERO;
+                        normal[axis] = -dir[axis].signum();
+                    }
+                    t_exit = t_exit.min(far);
+                }
+
+                if t_enter > t_exit || t_exit < 0. {
+                    return None;
+                }
+                if t_enter < 0. {
+                    // Already inside, push out from the centre
+                    return Some(ObstacleHit { distance: 0., normal: (origin - center).normalize_or_zero() });
+                }
+                Some(ObstacleHit { distance: t_enter, normal })
+            }
+            Obstacle::Plane { normal, offset } => {
+                let normal = normal.normalize_or_zero();
+                let height = (origin - center).dot(normal) - offset - margin;
+                if height <= 0. {
+                    return Some(ObstacleHit { distance: 0., normal });
+                }
+
+                let approach = -dir.dot(normal);
+                if approach <= 0. {
+                    return None;
+                }
+                Some(ObstacleHit { distance: height / approach, normal })
+            }
+        }
+    }
+}
+
+/// Looks `obstacle_look_ahead` units along each boid's velocity and steers away from the
+/// nearest obstacle in the way, harder the closer it is.
+pub(super) fn avoid_obstacles (
+    mut q_boids: Query<(&Transform, &Velocity, &Species, &mut SteeringForce)>,
+    q_obstacles: Query<(&Obstacle, &Transform)>,
+    settings: Res<BoidSettings>,
+    weights: Res<SteeringWeights>,
+    table: Res<SpeciesTable>,
+) {
+    if q_obstacles.is_empty() {
+        return;
+    }
+
+    for (trans, vel, species, mut steering) in q_boids.iter_mut() {
+        let forward = vel.0.normalize_or_zero();
+        let nearest = q_obstacles.iter()
+            .filter_map(|(obstacle, obstacle_trans)| {
+                obstacle.raycast(obstacle_trans.translation, trans.translation, forward, settings.obstacle_margin)
+            })
+            .filter(|hit| hit.distance <= settings.obstacle_look_ahead)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        if let Some(hit) = nearest {
+            // Slide along the surface, tipped away from it
+            let slide = forward - hit.normal * forward.dot(hit.normal);
+            let params = table.params(*species);
+            let urgency = 1. - hit.distance / settings.obstacle_look_ahead;
+            steering.add(SteeringRule::Obstacle, weights.obstacle * params.weights.obstacle * urgency, steer_towards(vel.0, slide + hit.normal, settings.max_speed * params.speed_factor));
+        }
+    }
+}
+
+/// Keeps everything with a velocity on the solid side of plane obstacles.
+pub(super) fn enforce_floors (
+    mut q_movers: Query<(Entity, &mut Transform, &mut Velocity, Option<&Boid>), Without<Obstacle>>,
+    q_obstacles: Query<(&Obstacle, &Transform)>,
+    mut grid: ResMut<GridMap>,
+) {
+    for (obstacle, obstacle_trans) in q_obstacles.iter() {
+        let (normal, offset) = match *obstacle {
+            Obstacle::Plane { normal, offset } => (normal.normalize_or_zero(), offset),
+            _ => continue,
+        };
+
+        for (entity, mut trans, mut vel, boid) in q_movers.iter_mut() {
+            let depth = (trans.translation - obstacle_trans.translation).dot(normal) - offset;
+            if depth >= 0. {
+                continue;
+            }
+
+            let prev = trans.translation;
+            trans.translation -= normal * depth;
+            let into_plane = vel.0.dot(normal).min(0.);
+            vel.0 -= normal * into_plane;
+            // Predators are held up too, but only boids are in the grid
+            if boid.is_some() {
+                grid.move_entity(entity, prev, trans.translation);
+            }
+        }
+    }
+}
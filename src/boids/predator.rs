@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use super::{bank_into_turn, facing, neighbours, steer_towards, Boid, BoidSettings, GridMap, Perception, SimulationRng, SimulationStep, Species, SpeciesTable, SteeringForce, SteeringRule, SteeringWeights, TargetVelocity, Velocity};
+
+/// A hawk that hunts the flock. Boids within `flee_radius` of it get a strong evasion force.
+#[derive(Component)]
+pub struct Predator;
+
+/// Keeps the number of predators in line with `BoidSettings::predator_count`, spawning or
+/// despawning only the difference and leaving the rest where they are.
+pub(super) fn sync_predator_count (
+    mut commands: Commands,
+    mut rng: ResMut<SimulationRng>,
+    settings: Res<BoidSettings>,
+    q_predators: Query<Entity, With<Predator>>,
+) {
+    let count = q_predators.iter().len();
+    let target = settings.predator_count as usize;
+    if !settings.is_changed() || count == target {
+        return;
+    }
+
+    if target > count {
+        for _ in count..target {
+            spawn_predator(&mut commands, &mut rng, &settings);
+        }
+    } else {
+        // The newest predators go first, as with boids when the flock shrinks
+        let mut predators: Vec<Entity> = q_predators.iter().collect();
+        predators.sort();
+        for entity in predators.into_iter().rev().take(count - target) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Spawns one predator at a random point inside the bounds, flying level.
+fn spawn_predator (
+    commands: &mut Commands,
+    rng: &mut SimulationRng,
+    settings: &BoidSettings,
+) {
+    let rng = &mut rng.0;
+    let bounds = settings.bounds;
+
+    let pos = Vec3::new(rng.gen_range(bounds[0].x..bounds[1].x), rng.gen_range(bounds[0].y..bounds[1].y), rng.gen_range(bounds[0].z..bounds[1].z));
+    let vel = Vec3::new(rng.gen_range(-1.0..1.0), 0., rng.gen_range(-1.0..1.0)).normalize_or_zero() * settings.predator_speed;
+
+    commands.spawn((
+        Predator,
+        Velocity(vel),
+        TargetVelocity(vel),
+        Transform::from_translation(pos).with_rotation(facing(vel)),
+        GlobalTransform::default(),
+        Name::new("Predator"),
+    ));
+}
+
+/// Pursues the nearest boid within `hunt_radius`, or the centroid of the flock when none is that close.
+pub(super) fn hunt (
+    mut q_predators: Query<(Entity, &Transform, &mut Velocity, &mut TargetVelocity), With<Predator>>,
+    q_boid_trans: Query<&Transform, With<Boid>>,
+    grid: Res<GridMap>,
+    settings: Res<BoidSettings>,
+    step: Res<SimulationStep>,
+) {
+    if q_predators.is_empty() || grid.is_empty() {
+        return;
+    }
+
+    let centroid = q_boid_trans.iter().map(|t| t.translation).sum::<Vec3>() / q_boid_trans.iter().len().max(1) as f32;
+
+    for (entity, trans, mut vel, mut target_vel) in q_predators.iter_mut() {
+        let position = |e| q_boid_trans.get(e).ok().map(|t| t.translation);
+        let nearest = neighbours(&grid, entity, trans.translation, vel.0, Perception::all_around(settings.hunt_radius), position)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        let target = nearest.map_or(centroid, |near| trans.translation + near.offset);
+        let steering = steer_towards(vel.0, target - trans.translation, settings.predator_speed)
+            .clamp_length_max(settings.predator_max_force);
+
+        target_vel.0 = vel.0 + steering;
+        vel.0 = (vel.0 + steering * step.delta).normalize_or_zero() * settings.predator_speed;
+    }
+}
+
+/// Pushes boids near a predator directly away from it, harder the closer they are.
+pub(super) fn flee_predators (
+    mut q_boids: Query<(&Velocity, &Species, &mut SteeringForce), With<Boid>>,
+    q_predators: Query<(Entity, &Transform), With<Predator>>,
+    q_boid_trans: Query<&Transform, With<Boid>>,
+    grid: Res<GridMap>,
+    settings: Res<BoidSettings>,
+    weights: Res<SteeringWeights>,
+    table: Res<SpeciesTable>,
+) {
+    let flee = Perception::all_around(settings.flee_radius);
+
+    for (predator, trans) in q_predators.iter() {
+        let position = |e| q_boid_trans.get(e).ok().map(|t| t.translation);
+
+        for near in neighbours(&grid, predator, trans.translation, Vec3::ZERO, flee, position) {
+            if let Ok((vel, species, mut steering)) = q_boids.get_mut(near.entity) {
+                let params = table.params(*species);
+                let urgency = 1. - near.distance / flee.radius;
+                steering.add(SteeringRule::Flee, weights.flee * params.weights.flee * urgency, steer_towards(vel.0, near.offset, settings.max_speed * params.speed_factor));
+            }
+        }
+    }
+}
+
+pub(super) fn move_predators (
+    mut q_predators: Query<(&mut Transform, &Velocity, &TargetVelocity), With<Predator>>,
+    settings: Res<BoidSettings>,
+    step: Res<SimulationStep>,
+) {
+    for (mut transform, velocity, target) in q_predators.iter_mut() {
+        transform.rotation = bank_into_turn(transform.rotation, velocity.0, target.0 - velocity.0, &settings, step.delta);
+        transform.translation += velocity.0 * step.delta;
+    }
+}
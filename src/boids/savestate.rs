@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{hierarchy::despawn_with_children_recursive, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::ron_file::{self, RonFileError};
+
+use super::{
+    Boid, BoidBundle, BoidSettings, GridMap, Predator, SimulationRng, SimulationStep, Species, SpeciesTable,
+    SteeringForce, SteeringWeights, TargetVelocity, Velocity,
+};
+
+/// Writes the flock to the file at the given path, see [`FlockSave`].
+pub struct SaveFlock(pub PathBuf);
+
+/// Replaces the flock with the one saved in the file at the given path, see [`FlockSave`].
+pub struct LoadFlock(pub PathBuf);
+
+/// Everything needed to put a flock back the way it was: every boid and predator along with the
+/// settings, weights and species table. Stored as RON.
+///
+/// A loaded flock flies on exactly as the saved one did. Only the random number generator, which
+/// is reseeded from the saved settings, starts over, so boids spawned after loading differ.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlockSave {
+    pub settings: BoidSettings,
+    pub weights: SteeringWeights,
+    pub species: SpeciesTable,
+    /// [`SimulationStep::count`] when saved
+    pub step: u64,
+    pub boids: Vec<SavedBoid>,
+    pub predators: Vec<SavedPredator>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SavedBoid {
+    pub species: Species,
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub velocity: Vec3,
+    pub target_velocity: Vec3,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SavedPredator {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub velocity: Vec3,
+}
+
+impl FlockSave {
+    /// Copies the current flock out of `world`. Boids are listed cell by cell in the order the
+    /// [`GridMap`] holds them, which [`restore`](Self::restore) rebuilds, since the rules sum up
+    /// neighbours in that order and a different one would round differently.
+    pub fn capture(world: &mut World) -> Self {
+        let mut q_boids = world.query_filtered::<(&Species, &Transform, &Velocity, &TargetVelocity), With<Boid>>();
+        let boids = world.resource::<GridMap>()
+            .cells()
+            .flatten()
+            .filter_map(|entity| q_boids.get(world, *entity).ok())
+            .map(|(species, trans, vel, target)| SavedBoid {
+                species: *species,
+                position: trans.translation,
+                rotation: trans.rotation,
+                velocity: vel.0,
+                target_velocity: target.0,
+            })
+            .collect();
+
+        let mut predators: Vec<_> = world
+            .query_filtered::<(Entity, &Transform, &Velocity), With<Predator>>()
+            .iter(world)
+            .map(|(entity, trans, vel)| (entity, SavedPredator {
+                position: trans.translation,
+                rotation: trans.rotation,
+                velocity: vel.0,
+            }))
+            .collect();
+        predators.sort_by_key(|(entity, _)| *entity);
+
+        Self {
+            settings: world.resource::<BoidSettings>().clone(),
+            weights: *world.resource::<SteeringWeights>(),
+            species: world.resource::<SpeciesTable>().clone(),
+            step: world.resource::<SimulationStep>().count,
+            boids,
+            predators: predators.into_iter().map(|(_, predator)| predator).collect(),
+        }
+    }
+
+    /// Replaces the flock in `world` with this one and rebuilds the [`GridMap`] around it,
+    /// inserting the boids in their saved order so every cell lists them as it did when saved.
+    pub fn restore(&self, world: &mut World) {
+        let old: Vec<Entity> = world
+            .query_filtered::<Entity, Or<(With<Boid>, With<Predator>)>>()
+            .iter(world)
+            .collect();
+        for entity in old {
+            despawn_with_children_recursive(world, entity);
+        }
+
+        // The counts follow the saved flock, so loading does not trigger a respawn
+        let mut settings = self.settings.clone();
+        settings.bird_count = self.boids.len() as u32;
+        settings.predator_count = self.predators.len() as u32;
+
+        let mut grid = GridMap::new(settings.bounds, settings.dimensions);
+        for boid in &self.boids {
+            let id = world.spawn((
+                BoidBundle {
+                    boid: Boid,
+                    species: boid.species,
+                    velocity: Velocity(boid.velocity),
+                    target: TargetVelocity(boid.target_velocity),
+                    steering: SteeringForce::default(),
+                    transform: Transform::from_translation(boid.position).with_rotation(boid.rotation),
+                    global_transform: GlobalTransform::default(),
+                },
+                Name::new("Boid"),
+            )).id();
+            grid.insert(id, boid.position);
+        }
+
+        for predator in &self.predators {
+            world.spawn((
+                Predator,
+                Velocity(predator.velocity),
+                TargetVelocity(predator.velocity),
+                Transform::from_translation(predator.position).with_rotation(predator.rotation),
+                GlobalTransform::default(),
+                Name::new("Predator"),
+            ));
+        }
+
+        world.insert_resource(grid);
+        world.insert_resource(SimulationRng::from_settings(&settings));
+        world.resource_mut::<SimulationStep>().count = self.step;
+        world.insert_resource(settings);
+        world.insert_resource(self.weights);
+        world.insert_resource(self.species.clone());
+    }
+
+    pub fn to_ron(&self) -> Result<String, RonFileError> {
+        ron_file::to_string(self)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, RonFileError> {
+        ron_file::from_str(text)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RonFileError> {
+        ron_file::save(self, path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RonFileError> {
+        ron_file::load(path)
+    }
+}
+
+/// Handles [`SaveFlock`] and [`LoadFlock`] events. Runs before the simulation so a loaded flock
+/// is fully in place, with no deferred spawns, when the next step starts.
+pub(super) fn handle_save_state_events(world: &mut World) {
+    let saves: Vec<PathBuf> = world.resource_mut::<Events<SaveFlock>>().drain().map(|event| event.0).collect();
+    for path in saves {
+        match FlockSave::capture(world).save(&path) {
+            Ok(()) => info!("Saved the flock to {}", path.display()),
+            Err(err) => error!("Could not save the flock to {}: {}", path.display(), err),
+        }
+    }
+
+    let loads: Vec<PathBuf> = world.resource_mut::<Events<LoadFlock>>().drain().map(|event| event.0).collect();
+    for path in loads {
+        match FlockSave::load(&path) {
+            Ok(save) => {
+                save.restore(world);
+                info!("Loaded the flock from {}", path.display());
+            }
+            Err(err) => error!("Could not load the flock from {}: {}", path.display(), err),
+        }
+    }
+}
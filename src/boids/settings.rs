@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{BoundaryMode, Perception, SpeciesParams};
+
+/// Simulation parameters, read by the boid systems every frame.
+/// Registered for reflection so the world inspector can tune them live.
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Debug)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct BoidSettings {
+    /// Speed boids are spawned with, and cruise at with `flight_physics`
+    pub speed: f32,
+    /// Stall speed with `flight_physics`
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// Upper bound on the length of the summed steering force
+    pub max_force: f32,
+    /// Global gain applied to the summed steering force
+    pub steering_factor: f32,
+    /// Changing this respawns the flock
+    pub bird_count: u32,
+    /// Boids closer than this push each other apart
+    pub separation_distance: f32,
+    pub perception_radius: f32,
+    /// Full view cone in degrees, 360 sees all around
+    pub field_of_view: f32,
+    /// Changing the bounds or dimensions rebuilds the grid
+    pub bounds: [Vec3; 2],
+    pub dimensions: [i32; 3],
+    /// How boids are kept inside the bounds
+    pub boundary: BoundaryMode,
+    /// Distance from the walls at which `SoftWalls`, `Sphere` and `Cylinder` start pushing boids back
+    pub wall_margin: f32,
+    /// Radius of the `Sphere` and `Cylinder` arenas, centred on the bounds
+    pub arena_radius: f32,
+    pub predator_count: u32,
+    pub predator_speed: f32,
+    pub predator_max_force: f32,
+    /// Predators chase the nearest boid within this radius, or the flock centroid
+    pub hunt_radius: f32,
+    /// Boids closer than this to a predator flee from it
+    pub flee_radius: f32,
+    /// Lets the speed vary with thrust, gravity and drag instead of only being clamped to the speed range
+    pub flight_physics: bool,
+    /// Most a boid can speed up or brake by itself with `flight_physics`, in units per second squared
+    pub max_acceleration: f32,
+    /// Quadratic drag coefficient with `flight_physics`
+    pub drag: f32,
+    /// Steepest bank in degrees when turning
+    pub max_bank: f32,
+    /// Fastest the nose turns towards the velocity, in degrees per second
+    pub pitch_rate: f32,
+    /// Fastest the wings roll into or out of a bank, in degrees per second
+    pub roll_rate: f32,
+    /// How far ahead along their velocity boids look for obstacles
+    pub obstacle_look_ahead: f32,
+    /// Clearance boids try to keep from obstacle surfaces
+    pub obstacle_margin: f32,
+    /// Step by exactly `timestep` every update and draw randomness from `seed`,
+    /// so the same seed and settings always produce the same trajectories
+    pub deterministic: bool,
+    pub timestep: f32,
+    pub seed: u64,
+}
+
+impl Default for BoidSettings {
+    fn default() -> Self {
+        Self {
+            speed: 10.,
+            min_speed: 6.,
+            max_speed: 14.,
+            max_force: 10.,
+            steering_factor: 1.,
+            bird_count: 2000,
+            separation_distance: 2.,
+            perception_radius: 6.,
+            field_of_view: 270.,
+            // The floor is just above the top of the ground in the scene, at y = -30,
+            // so no boid spawns or wraps around into it
+            bounds: [Vec3::new(-100., -25., -100.), Vec3::new(100., 100., 100.)],
+            dimensions: [20, 12, 20],
+            boundary: BoundaryMode::SoftWalls,
+            wall_margin: 20.,
+            arena_radius: 100.,
+            predator_count: 1,
+            predator_speed: 16.,
+            predator_max_force: 12.,
+            hunt_radius: 30.,
+            flee_radius: 15.,
+            flight_physics: false,
+            max_acceleration: 4.,
+            drag: 0.005,
+            max_bank: 60.,
+            pitch_rate: 270.,
+            roll_rate: 180.,
+            obstacle_look_ahead: 15.,
+            obstacle_margin: 2.,
+            deterministic: false,
+            timestep: 1. / 60.,
+            seed: 0,
+        }
+    }
+}
+
+impl BoidSettings {
+    /// Perception used by the alignment and cohesion rules
+    pub fn perception(&self) -> Perception {
+        Perception {
+            radius: self.perception_radius,
+            field_of_view: (self.field_of_view < 360.).then(|| self.field_of_view.to_radians()),
+        }
+    }
+
+    /// [`perception`](Self::perception) scaled to how far a species sees
+    pub fn perception_for(&self, params: &SpeciesParams) -> Perception {
+        Perception { radius: self.perception_radius * params.perception_factor, ..self.perception() }
+    }
+}
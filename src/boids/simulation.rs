@@ -0,0 +1,160 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::BoidSettings;
+use crate::GameState;
+
+/// Time step of the current simulation update.
+///
+/// In deterministic mode this is always `BoidSettings::timestep`, so a run only depends on
+/// the seed, the settings and the number of steps, never on the frame rate.
+#[derive(Resource, Default, Debug)]
+pub struct SimulationStep {
+    pub delta: f32,
+    /// Number of updates since the flock was spawned
+    pub count: u64,
+}
+
+/// Source of all randomness in the simulation.
+#[derive(Resource)]
+pub struct SimulationRng(pub StdRng);
+
+impl SimulationRng {
+    /// Seeded from `BoidSettings::seed` in deterministic mode, from entropy otherwise.
+    pub fn from_settings(settings: &BoidSettings) -> Self {
+        if settings.deterministic {
+            Self(StdRng::seed_from_u64(settings.seed))
+        } else {
+            Self(StdRng::from_entropy())
+        }
+    }
+}
+
+impl FromWorld for SimulationRng {
+    fn from_world(world: &mut World) -> Self {
+        Self::from_settings(world.resource::<BoidSettings>())
+    }
+}
+
+/// Shared run criteria of the simulation systems, see [`SimulationClock`].
+#[derive(RunCriteriaLabel, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SimulationSteps;
+
+/// Most steps run in one frame. A slower frame drops the rest of its time rather than falling further behind.
+const MAX_STEPS_PER_FRAME: u32 = 5;
+
+/// Decides how many simulation steps run each frame.
+///
+/// In deterministic mode the frame time is collected and spent in steps of exactly `BoidSettings::timestep`,
+/// so the flock flies at the same speed whatever the frame rate. Otherwise one step as long as the frame runs per frame.
+#[derive(Resource, Default, Debug)]
+pub struct SimulationClock {
+    /// Runs exactly one step per update, however long it took. Headless runs use this, so they only depend on the number of updates
+    pub lockstep: bool,
+    /// Frame time not yet spent on steps
+    accumulated: f32,
+    /// Steps still to run this frame, `None` until the frame's first step
+    remaining: Option<u32>,
+}
+
+impl SimulationClock {
+    pub fn lockstep() -> Self {
+        Self { lockstep: true, ..default() }
+    }
+
+    /// Adds `delta` seconds to the clock and takes the whole steps it now holds.
+    pub fn steps_due(&mut self, delta: f32, settings: &BoidSettings) -> u32 {
+        if self.lockstep || !settings.deterministic || settings.timestep <= 0. {
+            return 1;
+        }
+
+        self.accumulated += delta;
+        let steps = (self.accumulated / settings.timestep).floor() as u32;
+        if steps > MAX_STEPS_PER_FRAME {
+            self.accumulated = 0.;
+            return MAX_STEPS_PER_FRAME;
+        }
+        self.accumulated -= steps as f32 * settings.timestep;
+        steps
+    }
+}
+
+/// Runs the simulation systems once for every step due this frame, while playing.
+///
+/// Returning `YesAndCheckAgain` makes the stage run the systems again and ask once more,
+/// until the steps run out. `No` ends the frame, so the next call starts a new one.
+pub(super) fn run_simulation_steps (
+    state: Res<State<GameState>>,
+    settings: Res<BoidSettings>,
+    time: Res<Time>,
+    mut clock: ResMut<SimulationClock>,
+) -> ShouldRun {
+    if *state.current() != GameState::Playing {
+        // No catching up on the time spent elsewhere
+        clock.accumulated = 0.;
+        clock.remaining = None;
+        return ShouldRun::No;
+    }
+
+    let remaining = match clock.remaining {
+        Some(remaining) => remaining,
+        None => clock.steps_due(time.delta_seconds(), &settings),
+    };
+    if remaining == 0 {
+        clock.remaining = None;
+        ShouldRun::No
+    } else {
+        clock.remaining = Some(remaining - 1);
+        ShouldRun::YesAndCheckAgain
+    }
+}
+
+pub(super) fn advance_simulation_step (
+    mut step: ResMut<SimulationStep>,
+    settings: Res<BoidSettings>,
+    time: Res<Time>,
+) {
+    step.delta = if settings.deterministic { settings.timestep } else { time.delta_seconds() };
+    step.count += 1;
+}
+
+/// Runs flock systems outside the `Update` stage, where the criteria of [`SystemSet::on_update`] do not reach, while playing.
+pub(super) fn playing(state: Res<State<GameState>>) -> ShouldRun {
+    if *state.current() == GameState::Playing { ShouldRun::Yes } else { ShouldRun::No }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps_in_one_second(frame_rate: u32) -> u32 {
+        let settings = BoidSettings { deterministic: true, ..default() };
+        let mut clock = SimulationClock::default();
+        (0..frame_rate).map(|_| clock.steps_due(1. / frame_rate as f32, &settings)).sum()
+    }
+
+    #[test]
+    fn step_rate_does_not_depend_on_frame_rate() {
+        let expected = (1. / BoidSettings::default().timestep).round() as i64;
+        for frame_rate in [30, 60, 144, 240] {
+            let steps = steps_in_one_second(frame_rate) as i64;
+            assert!((steps - expected).abs() <= 1, "{} steps in a second at {} Hz", steps, frame_rate);
+        }
+    }
+
+    #[test]
+    fn slow_frames_are_capped() {
+        let settings = BoidSettings { deterministic: true, ..default() };
+        let mut clock = SimulationClock::default();
+        assert_eq!(clock.steps_due(2., &settings), MAX_STEPS_PER_FRAME);
+        assert_eq!(clock.steps_due(0., &settings), 0);
+    }
+
+    #[test]
+    fn lockstep_runs_one_step_per_update() {
+        let settings = BoidSettings { deterministic: true, ..default() };
+        let mut clock = SimulationClock::lockstep();
+        assert_eq!(clock.steps_due(0., &settings), 1);
+        assert_eq!(clock.steps_due(1., &settings), 1);
+    }
+}
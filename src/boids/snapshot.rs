@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use super::{neighbours::View, Boid, GridMap, Neighbour, Perception, Species, Velocity};
+
+/// State of one boid at the start of the step.
+#[derive(Clone, Copy, Debug)]
+pub struct BoidSample {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub species: Species,
+}
+
+/// A read-only copy of every boid, stored contiguously and grouped by [`GridMap`] cell.
+///
+/// Taken once per step in [`BoidSystem::Prepare`](super::BoidSystem::Prepare), so the steering rules
+/// can look up neighbours from many threads at once without touching the world. The buffers are
+/// reused between steps.
+#[derive(Resource, Default)]
+pub struct FlockSnapshot {
+    /// Boids of cell `i` are `boids[cell_starts[i]..cell_starts[i + 1]]`
+    cell_starts: Vec<usize>,
+    boids: Vec<BoidSample>,
+}
+
+impl FlockSnapshot {
+    /// Refills the snapshot from the boids in `grid`, in cell order.
+    pub fn rebuild<F>(&mut self, grid: &GridMap, sample: F)
+    where
+        F: Fn(Entity) -> Option<BoidSample>,
+    {
+        self.cell_starts.clear();
+        self.boids.clear();
+
+        for cell in grid.cells() {
+            self.cell_starts.push(self.boids.len());
+            self.boids.extend(cell.iter().filter_map(|entity| sample(*entity)));
+        }
+        self.cell_starts.push(self.boids.len());
+    }
+
+    pub fn len(&self) -> usize {
+        self.boids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boids.is_empty()
+    }
+
+    pub fn boids(&self) -> &[BoidSample] {
+        &self.boids
+    }
+
+    /// Boids in the cell at flat index `cell`, see [`GridMap::flat_index`].
+    pub fn cell(&self, cell: usize) -> &[BoidSample] {
+        match (self.cell_starts.get(cell), self.cell_starts.get(cell + 1)) {
+            (Some(&start), Some(&end)) => &self.boids[start..end],
+            _ => &[],
+        }
+    }
+
+    /// Same as [`neighbours`](super::neighbours), but reads positions from the snapshot instead of the world.
+    /// `grid` must be the grid the snapshot was taken from.
+    pub fn neighbours<'a>(
+        &'a self,
+        grid: &'a GridMap,
+        this: Entity,
+        pos: Vec3,
+        forward: Vec3,
+        perception: Perception,
+    ) -> impl Iterator<Item = (Neighbour, &'a BoidSample)> + 'a {
+        let view = View::new(perception, forward);
+
+        grid.indices_in_radius(pos, perception.radius)
+            .flat_map(move |index| self.cell(grid.flat_index(index)))
+            .filter(move |other| other.entity != this)
+            .filter_map(move |other| {
+                let offset = other.position - pos;
+                let distance = view.sees(offset)?;
+                Some((Neighbour { entity: other.entity, offset, distance }, other))
+            })
+    }
+}
+
+pub(super) fn take_flock_snapshot (
+    mut snapshot: ResMut<FlockSnapshot>,
+    grid: Res<GridMap>,
+    q_boids: Query<(&Transform, &Velocity, &Species), With<Boid>>,
+) {
+    snapshot.rebuild(&grid, |entity| {
+        let (trans, vel, species) = q_boids.get(entity).ok()?;
+        Some(BoidSample { entity, position: trans.translation, velocity: vel.0, species: *species })
+    });
+}
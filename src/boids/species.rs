@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::SteeringWeights;
+
+/// Which kind of bird a boid is. Boids only align with and gather around the species they flock with.
+#[derive(Component, Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Species {
+    #[default]
+    Starling,
+    Pigeon,
+    Gull,
+}
+
+impl Species {
+    pub const ALL: [Species; 3] = [Species::Starling, Species::Pigeon, Species::Gull];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How one species reacts to another when they meet.
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpeciesRelation {
+    /// Only keep the separation distance
+    #[default]
+    Ignore,
+    /// Keep the whole perception radius clear of them
+    Avoid,
+    /// Align with and gather around them as flockmates
+    Flock,
+}
+
+/// Per-species tuning, relative to the flock-wide [`BoidSettings`](super::BoidSettings) and [`SteeringWeights`].
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SpeciesParams {
+    /// Fraction of `bird_count` spawned as this species, applied when the flock is respawned
+    pub share: f32,
+    /// Scales the speed range
+    pub speed_factor: f32,
+    /// Scales the perception radius
+    pub perception_factor: f32,
+    /// Multiplies the weight of each steering rule
+    pub weights: SteeringWeights,
+    /// Size of the model
+    pub scale: f32,
+    /// Tint of the instanced models
+    pub color: Color,
+}
+
+/// Parameters of every species and how they treat each other, indexed by [`Species::index`].
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Debug)]
+#[reflect(Resource)]
+pub struct SpeciesTable {
+    pub params: [SpeciesParams; 3],
+    /// `relations[a][b]` is how species `a` treats species `b`
+    pub relations: [[SpeciesRelation; 3]; 3],
+}
+
+impl Default for SpeciesTable {
+    fn default() -> Self {
+        use SpeciesRelation::*;
+
+        Self {
+            params: [
+                // Starlings: small, quick and tightly packed
+                SpeciesParams {
+                    share: 0.6,
+                    speed_factor: 1.,
+                    perception_factor: 1.,
+                    weights: SteeringWeights { alignment: 1.2, cohesion: 1.2, ..SteeringWeights::splat(1.) },
+                    scale: 0.02,
+                    color: Color::rgb(0.15, 0.12, 0.2),
+                },
+                // Pigeons: slower, looser groups
+                SpeciesParams {
+                    share: 0.25,
+                    speed_factor: 0.8,
+                    perception_factor: 0.8,
+                    weights: SteeringWeights { cohesion: 0.8, level_flight: 1.5, ..SteeringWeights::splat(1.) },
+                    scale: 0.06,
+                    color: Color::rgb(0.55, 0.57, 0.65),
+                },
+                // Gulls: big, fast gliders that keep their distance
+                SpeciesParams {
+                    share: 0.15,
+                    speed_factor: 1.2,
+                    perception_factor: 1.5,
+                    weights: SteeringWeights { alignment: 0.6, cohesion: 0.4, separation: 2., ..SteeringWeights::splat(1.) },
+                    scale: 0.035,
+                    color: Color::rgb(0.95, 0.95, 0.92),
+                },
+            ],
+            relations: [
+                //              Starling Pigeon  Gull
+                /* Starling */ [Flock,  Ignore,  Avoid],
+                /* Pigeon   */ [Ignore, Flock,   Avoid],
+                /* Gull     */ [Ignore, Ignore,  Flock],
+            ],
+        }
+    }
+}
+
+impl SpeciesTable {
+    pub fn params(&self, species: Species) -> &SpeciesParams {
+        &self.params[species.index()]
+    }
+
+    pub fn relation(&self, species: Species, other: Species) -> SpeciesRelation {
+        self.relations[species.index()][other.index()]
+    }
+
+    /// Whether `species` avoids any other species, and so needs to look beyond the separation distance
+    pub fn avoids_any(&self, species: Species) -> bool {
+        self.relations[species.index()].contains(&SpeciesRelation::Avoid)
+    }
+
+    /// How many of `bird_count` boids belong to each species. The counts always add up to `bird_count`.
+    pub fn split(&self, bird_count: u32) -> [u32; 3] {
+        let total: f32 = self.params.iter().map(|p| p.share.max(0.)).sum();
+        let mut counts = [0; 3];
+        let mut assigned = 0;
+        let mut cumulative = 0.;
+        for (i, params) in self.params.iter().enumerate() {
+            cumulative += params.share.max(0.);
+            let end = if total > 0. { (bird_count as f32 * cumulative / total).round() as u32 } else { 0 };
+            counts[i] = end.min(bird_count) - assigned;
+            assigned = end.min(bird_count);
+        }
+        // Rounding or an all-zero table leaves the remainder with the first species
+        counts[0] += bird_count - assigned;
+        counts
+    }
+
+    /// A species drawn at random in proportion to the shares, or the first species for an all-zero table.
+    pub fn pick(&self, rng: &mut impl Rng) -> Species {
+        let total: f32 = self.params.iter().map(|p| p.share.max(0.)).sum();
+        if total <= 0. {
+            return Species::ALL[0];
+        }
+        let mut draw = rng.gen_range(0.0..total);
+        for (species, params) in Species::ALL.into_iter().zip(&self.params) {
+            draw -= params.share.max(0.);
+            if draw < 0. {
+                return species;
+            }
+        }
+        // Rounding can leave the draw just past the last share
+        Species::ALL.into_iter().rev().find(|species| self.params(*species).share > 0.).unwrap_or(Species::ALL[0])
+    }
+}
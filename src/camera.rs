@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use bevy_atmosphere::prelude::AtmosphereCamera;
-use crate::{GameState, actions::{Actions, self}};
+use crate::{GameState, actions::{Actions, ControlInput, GameControl, set_actions}, boids::{Boid, BoidSystem}, selection::SelectedBoid};
 use bevy::ecs::event::{Events, ManualEventReader};
 use bevy::input::mouse::MouseMotion;
 use bevy::window::CursorGrabMode;
@@ -10,10 +10,15 @@ pub struct CameraPlugin;
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app
+        .init_resource::<CameraMode>()
+        .init_resource::<CameraRig>()
         .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(setup_camera))
         .add_plugin(NoCameraPlayerPlugin)
-        // .add_system_set(SystemSet::on_update(GameState::Playing).with_system(rotate_camera))
-        ;
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(cycle_camera_mode.after(set_actions))
+                .with_system(drive_camera.after(cycle_camera_mode).after(BoidSystem::Move))
+        );
     }
 }
 
@@ -31,7 +36,190 @@ fn setup_camera(
     ));
 }
 
+/// How the camera moves, switched with [`Actions::cycle_camera`].
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Flown with the keyboard, mouse or gamepad
+    #[default]
+    FreeFly,
+    /// Circles the centre of the flock
+    Orbit,
+    /// Follows the [`SelectedBoid`] from behind
+    Chase,
+    /// Looks out of the [`SelectedBoid`]'s eyes
+    FirstPerson,
+}
+
+impl CameraMode {
+    fn next(self) -> Self {
+        match self {
+            CameraMode::FreeFly => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Chase,
+            CameraMode::Chase => CameraMode::FirstPerson,
+            CameraMode::FirstPerson => CameraMode::FreeFly,
+        }
+    }
+
+    fn follows_boid(self) -> bool {
+        matches!(self, CameraMode::Chase | CameraMode::FirstPerson)
+    }
+}
+
+/// Seconds a switch between camera modes, or between followed boids, takes
+const BLEND_TIME: f32 = 1.;
+/// Radians per second the orbiting camera circles the flock at
+const ORBIT_SPEED: f32 = 0.2;
+const ORBIT_RADIUS: f32 = 140.;
+const ORBIT_HEIGHT: f32 = 40.;
+/// How far behind and above its boid the chase camera flies
+const CHASE_DISTANCE: f32 = 6.;
+const CHASE_HEIGHT: f32 = 1.5;
+/// How quickly the smoothed camera catches up with where it should be, per second
+const FOLLOW_STIFFNESS: f32 = 4.;
+
+/// State the automatic camera modes carry from frame to frame.
+#[derive(Resource, Default)]
+struct CameraRig {
+    orbit_angle: f32,
+    /// Smoothed flock centroid the orbit looks at
+    focus: Option<Vec3>,
+    /// Smoothed chase camera position
+    chase_position: Option<Vec3>,
+    /// Smoothed first-person view direction
+    eye_rotation: Option<Quat>,
+    /// Where the camera was when the last switch started, and seconds since
+    blend_from: Option<Transform>,
+    blend_elapsed: f32,
+}
+
+impl CameraRig {
+    fn start_blend(&mut self, from: Transform) {
+        self.blend_from = Some(from);
+        self.blend_elapsed = 0.;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn cycle_camera_mode(
+    actions: Res<Actions>,
+    mut mode: ResMut<CameraMode>,
+    mut state: ResMut<InputState>,
+    mut rig: ResMut<CameraRig>,
+    mut selected: ResMut<SelectedBoid>,
+    query: Query<&Transform, With<FlyCam>>,
+    q_boids: Query<(Entity, &Transform), (With<Boid>, Without<FlyCam>)>,
+) {
+    if !actions.cycle_camera {
+        return;
+    }
+    *mode = mode.next();
+
+    let transform = match query.iter().next() {
+        Some(transform) => *transform,
+        None => return,
+    };
+
+    // Follow the boid nearest the camera when none has been picked yet
+    if mode.follows_boid() && selected.0.is_none() {
+        selected.0 = q_boids.iter()
+            .min_by(|(_, a), (_, b)| a.translation.distance_squared(transform.translation)
+                .total_cmp(&b.translation.distance_squared(transform.translation)))
+            .map(|(entity, _)| entity);
+    }
+
+    rig.chase_position = None;
+    rig.eye_rotation = None;
+    rig.focus = None;
+    if *mode == CameraMode::FreeFly {
+        // Carry on flying from wherever the last mode left the camera
+        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        state.yaw = yaw;
+        state.pitch = pitch;
+        rig.blend_from = None;
+    } else {
+        rig.start_blend(transform);
+    }
+}
+
+/// Moves the camera in the orbit, chase and first-person modes, blending from
+/// where it was whenever the mode or the followed boid changes.
+fn drive_camera(
+    mode: Res<CameraMode>,
+    selected: Res<SelectedBoid>,
+    time: Res<Time>,
+    mut rig: ResMut<CameraRig>,
+    mut query: Query<&mut Transform, With<FlyCam>>,
+    q_boids: Query<&Transform, (With<Boid>, Without<FlyCam>)>,
+) {
+    if *mode == CameraMode::FreeFly {
+        return;
+    }
+    let mut transform = match query.iter_mut().next() {
+        Some(transform) => transform,
+        None => return,
+    };
+    let delta = time.delta_seconds();
+    let follow = 1. - (-FOLLOW_STIFFNESS * delta).exp();
+
+    if selected.is_changed() && mode.follows_boid() {
+        rig.start_blend(*transform);
+        rig.chase_position = None;
+        rig.eye_rotation = None;
+    }
+
+    let target = match *mode {
+        CameraMode::FreeFly => return,
+        CameraMode::Orbit => {
+            if q_boids.is_empty() {
+                return;
+            }
+            let centroid = q_boids.iter().map(|trans| trans.translation).sum::<Vec3>() / q_boids.iter().len() as f32;
+            let focus = rig.focus.map_or(centroid, |focus| focus.lerp(centroid, follow));
+            rig.focus = Some(focus);
+            rig.orbit_angle = (rig.orbit_angle + ORBIT_SPEED * delta) % std::f32::consts::TAU;
+
+            let offset = Vec3::new(rig.orbit_angle.cos() * ORBIT_RADIUS, ORBIT_HEIGHT, rig.orbit_angle.sin() * ORBIT_RADIUS);
+            Transform::from_translation(focus + offset).looking_at(focus, Vec3::Y)
+        }
+        CameraMode::Chase | CameraMode::FirstPerson => {
+            let boid = match selected.0.and_then(|entity| q_boids.get(entity).ok()) {
+                Some(boid) => boid,
+                None => return,
+            };
+            // The bird models look along +Z, cameras along -Z
+            let forward = boid.rotation * Vec3::Z;
 
+            if *mode == CameraMode::Chase {
+                let wanted = boid.translation - forward * CHASE_DISTANCE + Vec3::Y * CHASE_HEIGHT;
+                let position = rig.chase_position.map_or(wanted, |position| position.lerp(wanted, follow));
+                rig.chase_position = Some(position);
+                Transform::from_translation(position).looking_at(boid.translation, Vec3::Y)
+            } else {
+                // Smooth the banking out of the view a little, but keep up with the bird itself
+                let wanted = boid.rotation * Quat::from_rotation_y(std::f32::consts::PI);
+                let rotation = rig.eye_rotation.map_or(wanted, |rotation| rotation.slerp(wanted, follow));
+                rig.eye_rotation = Some(rotation);
+                Transform::from_translation(boid.translation + forward * 0.2).with_rotation(rotation)
+            }
+        }
+    };
+
+    match rig.blend_from {
+        Some(from) if rig.blend_elapsed < BLEND_TIME => {
+            rig.blend_elapsed += delta;
+            let t = (rig.blend_elapsed / BLEND_TIME).min(1.);
+            // Ease in and out
+            let t = t * t * (3. - 2. * t);
+            transform.translation = from.translation.lerp(target.translation, t);
+            transform.rotation = from.rotation.slerp(target.rotation, t);
+        }
+        _ => {
+            rig.blend_from = None;
+            transform.translation = target.translation;
+            transform.rotation = target.rotation;
+        }
+    }
+}
 
 /// Keeps track of mouse motion events, pitch, and yaw
 #[derive(Resource, Default)]
@@ -41,11 +229,13 @@ struct InputState {
     yaw: f32,
 }
 
-/// Mouse sensitivity and movement speed
+/// Mouse and gamepad sensitivity and movement speed
 
 #[derive(Resource)]
 pub struct MovementSettings {
     pub sensitivity: f32,
+    /// Turn rate with the look stick fully pushed, in radians per second
+    pub stick_sensitivity: f32,
     pub speed: f32,
 }
 
@@ -53,6 +243,7 @@ impl Default for MovementSettings {
     fn default() -> Self {
         Self {
             sensitivity: 0.00012,
+            stick_sensitivity: 2.,
             speed: 12.,
         }
     }
@@ -96,37 +287,34 @@ fn setup_player(mut commands: Commands) {
     ));
 }
 
-/// Handles keyboard input and movement
+/// Handles movement from the bound keys and sticks
 fn player_move(
-    keys: Res<Input<KeyCode>>,
+    input: ControlInput,
     time: Res<Time>,
     windows: Res<Windows>,
     settings: Res<MovementSettings>,
+    mode: Res<CameraMode>,
     mut query: Query<&mut Transform, With<FlyCam>>,
 ) {
+    if *mode != CameraMode::FreeFly {
+        return;
+    }
     if let Some(window) = windows.get_primary() {
         for mut transform in query.iter_mut() {
-            let mut velocity = Vec3::ZERO;
-            let local_z = transform.local_z();
-            let forward = -Vec3::new(local_z.x, 0., local_z.z);
-            let right = Vec3::new(local_z.z, 0., -local_z.x);
-
-            for key in keys.get_pressed() {
-                match window.cursor_grab_mode() {
-                    CursorGrabMode::None => (),
-                    _ => match key {
-                        KeyCode::W => velocity += forward,
-                        KeyCode::S => velocity -= forward,
-                        KeyCode::A => velocity -= right,
-                        KeyCode::D => velocity += right,
-                        KeyCode::Space => velocity += Vec3::Y,
-                        KeyCode::LShift => velocity -= Vec3::Y,
-                        _ => (),
-                    },
-                }
+            if window.cursor_grab_mode() == CursorGrabMode::None {
+                continue;
             }
 
-            velocity = velocity.normalize_or_zero();
+            let local_z = transform.local_z();
+            let forward = -Vec3::new(local_z.x, 0., local_z.z).normalize_or_zero();
+            let right = Vec3::new(local_z.z, 0., -local_z.x).normalize_or_zero();
+
+            let axis = |positive, negative| input.value(positive) - input.value(negative);
+            let velocity = (forward * axis(GameControl::FlyForward, GameControl::FlyBackward)
+                + right * axis(GameControl::FlyRight, GameControl::FlyLeft)
+                + Vec3::Y * axis(GameControl::FlyUp, GameControl::FlyDown))
+                // Sticks fly slower when only pushed part of the way
+                .clamp_length_max(1.);
 
             transform.translation += velocity * time.delta_seconds() * settings.speed
         }
@@ -135,32 +323,55 @@ fn player_move(
     }
 }
 
-/// Handles looking around if cursor is locked
+/// Handles looking around with the mouse and the bound sticks if cursor is locked
+#[allow(clippy::too_many_arguments)]
 fn player_look(
     settings: Res<MovementSettings>,
     windows: Res<Windows>,
     mut state: ResMut<InputState>,
     motion: Res<Events<MouseMotion>>,
+    input: ControlInput,
+    time: Res<Time>,
+    mode: Res<CameraMode>,
     mut query: Query<&mut Transform, With<FlyCam>>,
 ) {
+    if *mode != CameraMode::FreeFly {
+        // Drop the motion so the view does not jump when flying again
+        state.reader_motion.iter(&motion).last();
+        return;
+    }
     if let Some(window) = windows.get_primary() {
         let mut delta_state = state.as_mut();
-        for mut transform in query.iter_mut() {
-            for ev in delta_state.reader_motion.iter(&motion) {
-                match window.cursor_grab_mode() {
-                    CursorGrabMode::None => (),
-                    _ => {
-                        // Using smallest of height or width ensures equal vertical and horizontal sensitivity
-                        let window_scale = window.height().min(window.width());
-                        delta_state.pitch -=
-                            (settings.sensitivity * ev.delta.y * window_scale).to_radians();
-                        delta_state.yaw -=
-                            (settings.sensitivity * ev.delta.x * window_scale).to_radians();
-                    }
-                }
-
-                delta_state.pitch = delta_state.pitch.clamp(-1.54, 1.54);
+        let grabbed = window.cursor_grab_mode() != CursorGrabMode::None;
+        let mut turned = false;
+
+        for ev in delta_state.reader_motion.iter(&motion) {
+            if grabbed {
+                // Using smallest of height or width ensures equal vertical and horizontal sensitivity
+                let window_scale = window.height().min(window.width());
+                delta_state.pitch -=
+                    (settings.sensitivity * ev.delta.y * window_scale).to_radians();
+                delta_state.yaw -=
+                    (settings.sensitivity * ev.delta.x * window_scale).to_radians();
+                turned = true;
+            }
+        }
 
+        let stick = Vec2::new(
+            input.value(GameControl::LookRight) - input.value(GameControl::LookLeft),
+            input.value(GameControl::LookUp) - input.value(GameControl::LookDown),
+        );
+        if grabbed && stick != Vec2::ZERO {
+            let turn = stick * settings.stick_sensitivity * time.delta_seconds();
+            delta_state.pitch += turn.y;
+            delta_state.yaw -= turn.x;
+            turned = true;
+        }
+
+        if turned {
+            delta_state.pitch = delta_state.pitch.clamp(-1.54, 1.54);
+
+            for mut transform in query.iter_mut() {
                 // Order is important to prevent unintended roll
                 transform.rotation = Quat::from_axis_angle(Vec3::Y, delta_state.yaw)
                     * Quat::from_axis_angle(Vec3::X, delta_state.pitch);
@@ -171,9 +382,9 @@ fn player_look(
     }
 }
 
-fn cursor_grab(keys: Res<Input<KeyCode>>, mut windows: ResMut<Windows>) {
+fn cursor_grab(input: ControlInput, mut windows: ResMut<Windows>) {
     if let Some(window) = windows.get_primary_mut() {
-        if keys.just_pressed(KeyCode::Escape) {
+        if input.just_pressed(GameControl::ToggleCursor) {
             toggle_grab_cursor(window);
         }
     } else {
@@ -204,25 +415,3 @@ impl Plugin for NoCameraPlayerPlugin {
             .add_system(cursor_grab);
     }
 }
-
-fn rotate_camera(
-    mut query: Query<&mut Transform, With<Camera>>,
-    time: Res<Time>,
-    keyboard_input: Res<Input<KeyCode>>,
-) {
-    let mut movement = 0.0;
-
-    if keyboard_input.pressed(KeyCode::Left) {
-        movement = -1.0;
-    } else if keyboard_input.pressed(KeyCode::Right) {
-        movement = 1.0;
-    } else {
-        return;
-    }
-
-    let mut cam_transform = query.single_mut();
-    let focus = Vec3::new(0., 1., 0.);
-
-    cam_transform.translate_around(focus, Quat::from_rotation_y(time.delta_seconds() * movement ));
-    cam_transform.look_at(focus, Vec3::Y)
-}
\ No newline at end of file
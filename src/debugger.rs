@@ -1,35 +1,319 @@
-use bevy::{prelude::*, time};
+use std::f32::consts::TAU;
 
-use crate::{GameState, boids::TargetVelocity};
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{mesh::PrimitiveTopology, view::NoFrustumCulling},
+};
+
+use crate::{
+    GameState,
+    actions::{ControlInput, GameControl},
+    boids::{
+        Boid, BoidSettings, BoidSystem, FlockSnapshot, GridMap, Species, SpeciesRelation, SpeciesTable,
+        TargetVelocity, Velocity,
+    },
+    selection::SelectedBoid,
+};
+
+/// Line overlays for looking inside the simulation, each toggled by its own hotkey.
 pub struct DebugPlugin;
 
 impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
         app
-        // .add_system_set(SystemSet::on_update(GameState::Playing).with_system(print_target_vel))
-        // .add_system_set(SystemSet::on_update(GameState::Playing).with_system(stop_after_timer))
-        .insert_resource(StopTimer(Timer::from_seconds(0.2, TimerMode::Once)))
+        .init_resource::<DebugOverlays>()
+        .register_type::<DebugOverlays>()
+        .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_overlays))
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(toggle_overlays)
+                .with_system(draw_overlays.after(toggle_overlays).after(BoidSystem::Move))
+        )
         ;
     }
 }
 
-fn print_target_vel (
-    query: Query<&TargetVelocity>
+/// Which debug overlays are drawn.
+#[derive(Resource, Reflect, Default, Debug)]
+#[reflect(Resource)]
+pub struct DebugOverlays {
+    /// The [`GridMap`] cells, occupied ones coloured from blue to red by how many boids they hold
+    pub grid: bool,
+    /// Each boid's velocity and target velocity
+    pub velocity: bool,
+    /// Perception sphere of the selected boid, linked to the neighbours it sees
+    pub perception: bool,
+    /// The simulation bounds
+    pub bounds: bool,
+}
+
+impl DebugOverlays {
+    pub fn shows(&self, overlay: DebugOverlay) -> bool {
+        match overlay {
+            DebugOverlay::Grid => self.grid,
+            DebugOverlay::Velocity => self.velocity,
+            DebugOverlay::Perception => self.perception,
+            DebugOverlay::Bounds => self.bounds,
+        }
+    }
+
+    pub fn toggle(&mut self, overlay: DebugOverlay) {
+        let shown = match overlay {
+            DebugOverlay::Grid => &mut self.grid,
+            DebugOverlay::Velocity => &mut self.velocity,
+            DebugOverlay::Perception => &mut self.perception,
+            DebugOverlay::Bounds => &mut self.bounds,
+        };
+        *shown = !*shown;
+    }
+}
+
+/// One of the debug overlays, also marking the entity that holds its line mesh
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugOverlay {
+    Grid,
+    Velocity,
+    Perception,
+    Bounds,
+}
+
+impl DebugOverlay {
+    pub const ALL: [DebugOverlay; 4] = [DebugOverlay::Grid, DebugOverlay::Velocity, DebugOverlay::Perception, DebugOverlay::Bounds];
+
+    fn control(self) -> GameControl {
+        match self {
+            DebugOverlay::Grid => GameControl::ToggleGridOverlay,
+            DebugOverlay::Velocity => GameControl::ToggleVelocityOverlay,
+            DebugOverlay::Perception => GameControl::TogglePerceptionOverlay,
+            DebugOverlay::Bounds => GameControl::ToggleBoundsOverlay,
+        }
+    }
+}
+
+/// Seconds of flight the velocity arrows are long
+const ARROW_SCALE: f32 = 0.25;
+
+/// Segments of each circle of the perception sphere
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Vertices of a line list mesh, two per line.
+#[derive(Default)]
+struct Lines {
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+}
+
+impl Lines {
+    fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn line(&mut self, start: Vec3, end: Vec3, color: Color) {
+        let color = color.as_linear_rgba_f32();
+        self.positions.extend([start.to_array(), end.to_array()]);
+        self.colors.extend([color, color]);
+    }
+
+    /// `vector` drawn from `start`, with a small head at the tip
+    fn arrow(&mut self, start: Vec3, vector: Vec3, color: Color) {
+        let tip = start + vector;
+        let side = vector.normalize_or_zero().any_orthonormal_vector() * vector.length() * 0.1;
+        let back = tip - vector * 0.2;
+        self.line(start, tip, color);
+        self.line(tip, back + side, color);
+        self.line(tip, back - side, color);
+    }
+
+    /// The twelve edges of the axis aligned box from `min` to `max`
+    fn cuboid(&mut self, min: Vec3, max: Vec3, color: Color) {
+        let corner = |x: bool, y: bool, z: bool| Vec3::select(BVec3::new(x, y, z), max, min);
+        for a in [false, true] {
+            for b in [false, true] {
+                self.line(corner(false, a, b), corner(true, a, b), color);
+                self.line(corner(a, false, b), corner(a, true, b), color);
+                self.line(corner(a, b, false), corner(a, b, true), color);
+            }
+        }
+    }
+
+    /// Three great circles, one around each axis
+    fn sphere(&mut self, center: Vec3, radius: f32, color: Color) {
+        for (u, v) in [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)] {
+            let point = |i: usize| {
+                let angle = TAU * i as f32 / CIRCLE_SEGMENTS as f32;
+                center + (u * angle.cos() + v * angle.sin()) * radius
+            };
+            for i in 0..CIRCLE_SEGMENTS {
+                self.line(point(i), point(i + 1), color);
+            }
+        }
+    }
+
+    fn write_to(self, mesh: &mut Mesh) {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+    }
+}
+
+fn spawn_overlays(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    for tv in query.iter() {
-        info!("TARGET VEL: {:?}", tv);
+    // Colours come from the vertices
+    let material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    });
+
+    for overlay in DebugOverlay::ALL {
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(Mesh::new(PrimitiveTopology::LineList)),
+                material: material.clone(),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            },
+            overlay,
+            // The lines change every frame, so their bounds would go stale
+            NoFrustumCulling,
+            NotShadowCaster,
+            Name::new(format!("{:?} Overlay", overlay)),
+        ));
     }
 }
 
-#[derive(Resource)]
-struct StopTimer(Timer);
+fn toggle_overlays(
+    input: ControlInput,
+    mut overlays: ResMut<DebugOverlays>,
+) {
+    for overlay in DebugOverlay::ALL {
+        if input.just_pressed(overlay.control()) {
+            overlays.toggle(overlay);
+        }
+    }
+}
 
-fn stop_after_timer (
-    mut timer: ResMut<StopTimer>,
-    time: Res<Time>,
-    mut state: ResMut<State<GameState>>
+/// Rebuilds the line mesh of every shown overlay
+#[allow(clippy::too_many_arguments)]
+fn draw_overlays(
+    overlays: Res<DebugOverlays>,
+    settings: Res<BoidSettings>,
+    grid: Res<GridMap>,
+    snapshot: Res<FlockSnapshot>,
+    table: Res<SpeciesTable>,
+    selected: Res<SelectedBoid>,
+    q_boids: Query<(&Transform, &Velocity, &TargetVelocity, &Species), With<Boid>>,
+    mut q_overlays: Query<(&DebugOverlay, &Handle<Mesh>, &mut Visibility)>,
+    mut meshes: ResMut<Assets<Mesh>>,
 ) {
-    if timer.0.tick(time.delta()).just_finished() {
-        state.set(GameState::Pause).unwrap();
+    for (overlay, handle, mut visibility) in q_overlays.iter_mut() {
+        let mut lines = Lines::default();
+        if overlays.shows(*overlay) {
+            match overlay {
+                DebugOverlay::Grid => grid_lines(&mut lines, &grid),
+                DebugOverlay::Velocity => {
+                    for (trans, vel, target, _) in q_boids.iter() {
+                        lines.arrow(trans.translation, vel.0 * ARROW_SCALE, Color::GREEN);
+                        lines.arrow(trans.translation, target.0 * ARROW_SCALE, Color::YELLOW);
+                    }
+                }
+                DebugOverlay::Perception => {
+                    if let Some(entity) = selected.0 {
+                        perception_lines(&mut lines, entity, &grid, &snapshot, &settings, &table, &q_boids);
+                    }
+                }
+                DebugOverlay::Bounds => lines.cuboid(settings.bounds[0], settings.bounds[1], Color::ORANGE),
+            }
+        }
+
+        // An empty mesh has nothing to draw and no vertex buffer to draw it from
+        visibility.is_visible = !lines.is_empty();
+        if visibility.is_visible {
+            if let Some(mesh) = meshes.get_mut(handle) {
+                lines.write_to(mesh);
+            }
+        }
     }
-}
\ No newline at end of file
+}
+
+/// A faint lattice of every cell, and a box inside each occupied cell coloured by its occupancy
+fn grid_lines(lines: &mut Lines, grid: &GridMap) {
+    let min = grid.bounds()[0];
+    let size = grid.cell_size();
+    let dims = grid.dimensions();
+    let lattice = Color::rgba(0.6, 0.6, 0.6, 0.15);
+
+    let at = |x: i32, y: i32, z: i32| min + Vec3::new(x as f32, y as f32, z as f32) * size;
+    for a in 0..=dims.y {
+        for b in 0..=dims.z {
+            lines.line(at(0, a, b), at(dims.x, a, b), lattice);
+        }
+    }
+    for a in 0..=dims.x {
+        for b in 0..=dims.z {
+            lines.line(at(a, 0, b), at(a, dims.y, b), lattice);
+        }
+        for b in 0..=dims.y {
+            lines.line(at(a, b, 0), at(a, b, dims.z), lattice);
+        }
+    }
+
+    let busiest = grid.cells().map(|cell| cell.len()).max().unwrap_or(0);
+    if busiest == 0 {
+        return;
+    }
+    let inset = size * 0.1;
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let count = grid.query_cell(IVec3::new(x, y, z)).len();
+                if count == 0 {
+                    continue;
+                }
+                // Blue for a single boid up to red for the busiest cell
+                let heat = count as f32 / busiest as f32;
+                let corner = at(x, y, z);
+                lines.cuboid(corner + inset, corner + size - inset, Color::hsla(240. * (1. - heat), 1., 0.5, 0.8));
+            }
+        }
+    }
+}
+
+/// The perception sphere of `entity` and a line to every neighbour it saw this step,
+/// cyan for flockmates, red for species it avoids and grey for the rest
+fn perception_lines(
+    lines: &mut Lines,
+    entity: Entity,
+    grid: &GridMap,
+    snapshot: &FlockSnapshot,
+    settings: &BoidSettings,
+    table: &SpeciesTable,
+    q_boids: &Query<(&Transform, &Velocity, &TargetVelocity, &Species), With<Boid>>,
+) {
+    let (trans, _, _, species) = match q_boids.get(entity) {
+        Ok(boid) => boid,
+        Err(_) => return,
+    };
+    let perception = settings.perception_for(table.params(*species));
+    lines.sphere(trans.translation, perception.radius, Color::WHITE);
+
+    // Who it saw is decided by the snapshot, but the links go to where the boids are now
+    let sample = match snapshot.boids().iter().find(|sample| sample.entity == entity) {
+        Some(sample) => sample,
+        None => return,
+    };
+    for (near, other) in snapshot.neighbours(grid, entity, sample.position, sample.velocity, perception) {
+        let color = match table.relation(*species, other.species) {
+            SpeciesRelation::Flock => Color::CYAN,
+            SpeciesRelation::Avoid => Color::RED,
+            SpeciesRelation::Ignore => Color::GRAY,
+        };
+        if let Ok((other_trans, ..)) = q_boids.get(near.entity) {
+            lines.line(trans.translation, other_trans.translation, color);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::boids::{neighbours, Boid, BoidSettings, BoidSystem, GridMap, Perception, SimulationStep, SimulationSteps, Velocity};
+
+/// Registers collective-motion metrics of the flock as [`Diagnostic`]s,
+/// so they show up in the log with `LogDiagnosticsPlugin`.
+pub struct FlockDiagnosticsPlugin {
+    /// Measure on every n-th simulation step, however many of them a frame takes. The cluster
+    /// and nearest-neighbour metrics visit every boid's neighbourhood, so this keeps their cost down.
+    pub every_n_steps: u32,
+}
+
+impl Default for FlockDiagnosticsPlugin {
+    fn default() -> Self {
+        Self { every_n_steps: 10 }
+    }
+}
+
+#[derive(Resource)]
+struct MeasureInterval(u64);
+
+impl Plugin for FlockDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Diagnostics>()
+            .insert_resource(MeasureInterval(self.every_n_steps.max(1) as u64))
+            .add_startup_system(Self::setup_system)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(SimulationSteps)
+                    .with_system(Self::diagnostic_system.after(BoidSystem::Move))
+            );
+    }
+}
+
+impl FlockDiagnosticsPlugin {
+    pub const POLARIZATION: DiagnosticId = DiagnosticId::from_u128(99327436713450815738917385810925630170);
+    pub const MEAN_SPEED: DiagnosticId = DiagnosticId::from_u128(254003916364926211364584702919410226785);
+    pub const NEAREST_NEIGHBOUR: DiagnosticId = DiagnosticId::from_u128(147224359542734823015939446339101567489);
+    pub const CENTROID_X: DiagnosticId = DiagnosticId::from_u128(314573226437934590873540516512315418961);
+    pub const CENTROID_Y: DiagnosticId = DiagnosticId::from_u128(45095377262905836264466391245720811237);
+    pub const CENTROID_Z: DiagnosticId = DiagnosticId::from_u128(193776011542386432009361346017520961880);
+    pub const FLOCK_RADIUS: DiagnosticId = DiagnosticId::from_u128(78014938205591316406398224702734826214);
+    pub const CLUSTERS: DiagnosticId = DiagnosticId::from_u128(280311553218014553941062908713961924519);
+    pub const OCCUPIED_CELLS: DiagnosticId = DiagnosticId::from_u128(124939578474617358622453081497012650931);
+    pub const MAX_CELL_OCCUPANCY: DiagnosticId = DiagnosticId::from_u128(335771418406208473601810419634860097305);
+
+    pub fn setup_system(mut diagnostics: ResMut<Diagnostics>) {
+        diagnostics.add(Diagnostic::new(Self::POLARIZATION, "flock_polarization", 20));
+        diagnostics.add(Diagnostic::new(Self::MEAN_SPEED, "flock_mean_speed", 20));
+        diagnostics.add(Diagnostic::new(Self::NEAREST_NEIGHBOUR, "flock_nearest_neighbour", 20));
+        diagnostics.add(Diagnostic::new(Self::CENTROID_X, "flock_centroid_x", 1).with_smoothing_factor(0.0));
+        diagnostics.add(Diagnostic::new(Self::CENTROID_Y, "flock_centroid_y", 1).with_smoothing_factor(0.0));
+        diagnostics.add(Diagnostic::new(Self::CENTROID_Z, "flock_centroid_z", 1).with_smoothing_factor(0.0));
+        diagnostics.add(Diagnostic::new(Self::FLOCK_RADIUS, "flock_radius", 20));
+        diagnostics.add(Diagnostic::new(Self::CLUSTERS, "flock_clusters", 20));
+        diagnostics.add(Diagnostic::new(Self::OCCUPIED_CELLS, "grid_occupied_cells", 20));
+        diagnostics.add(Diagnostic::new(Self::MAX_CELL_OCCUPANCY, "grid_max_cell_occupancy", 20));
+    }
+
+    fn diagnostic_system(
+        mut diagnostics: ResMut<Diagnostics>,
+        step: Res<SimulationStep>,
+        interval: Res<MeasureInterval>,
+        grid: Res<GridMap>,
+        settings: Res<BoidSettings>,
+        q_boids: Query<(Entity, &Transform, &Velocity), With<Boid>>,
+    ) {
+        if step.count % interval.0 != 0 {
+            return;
+        }
+
+        let boids: Vec<_> = q_boids.iter().map(|(e, t, v)| (e, t.translation, v.0)).collect();
+        let metrics = match FlockMetrics::measure(&grid, &boids, settings.perception_radius) {
+            Some(metrics) => metrics,
+            None => return,
+        };
+
+        diagnostics.add_measurement(Self::POLARIZATION, || metrics.polarization as f64);
+        diagnostics.add_measurement(Self::MEAN_SPEED, || metrics.mean_speed as f64);
+        diagnostics.add_measurement(Self::NEAREST_NEIGHBOUR, || metrics.mean_nearest_neighbour as f64);
+        diagnostics.add_measurement(Self::CENTROID_X, || metrics.centroid.x as f64);
+        diagnostics.add_measurement(Self::CENTROID_Y, || metrics.centroid.y as f64);
+        diagnostics.add_measurement(Self::CENTROID_Z, || metrics.centroid.z as f64);
+        diagnostics.add_measurement(Self::FLOCK_RADIUS, || metrics.radius as f64);
+        diagnostics.add_measurement(Self::CLUSTERS, || metrics.clusters as f64);
+        diagnostics.add_measurement(Self::OCCUPIED_CELLS, || metrics.occupied_cells as f64);
+        diagnostics.add_measurement(Self::MAX_CELL_OCCUPANCY, || metrics.max_cell_occupancy as f64);
+    }
+}
+
+/// Standard collective-motion measures of a flock at one instant.
+#[derive(Clone, Debug, Default)]
+pub struct FlockMetrics {
+    /// Length of the mean heading, 1 when every boid flies the same way and close to 0 when disordered
+    pub polarization: f32,
+    pub mean_speed: f32,
+    /// Average distance from each boid to its closest flockmate
+    pub mean_nearest_neighbour: f32,
+    pub centroid: Vec3,
+    /// Radius of gyration around the centroid
+    pub radius: f32,
+    /// Groups of boids connected through chains of flockmates within the link radius
+    pub clusters: usize,
+    pub occupied_cells: usize,
+    pub max_cell_occupancy: usize,
+}
+
+impl FlockMetrics {
+    /// Measures `boids`, given as (entity, position, velocity). Boids closer than `link_radius`
+    /// belong to the same cluster. Returns `None` for an empty flock.
+    pub fn measure(grid: &GridMap, boids: &[(Entity, Vec3, Vec3)], link_radius: f32) -> Option<Self> {
+        if boids.is_empty() {
+            return None;
+        }
+        let n = boids.len() as f32;
+
+        let index: HashMap<Entity, usize> = boids.iter().enumerate().map(|(i, (e, ..))| (*e, i)).collect();
+        let position = |e: Entity| index.get(&e).map(|&i| boids[i].1);
+
+        let heading_sum: Vec3 = boids.iter().map(|(.., v)| v.normalize_or_zero()).sum();
+        let speed_sum: f32 = boids.iter().map(|(.., v)| v.length()).sum();
+        let centroid = boids.iter().map(|(_, p, _)| *p).sum::<Vec3>() / n;
+        let gyration = boids.iter().map(|(_, p, _)| p.distance_squared(centroid)).sum::<f32>() / n;
+
+        // Nearest neighbours, widening the search until something is found or the whole grid is covered
+        let start_radius = grid.cell_size().max_element();
+        let max_radius = (grid.bounds()[1] - grid.bounds()[0]).length();
+        let mut nn_sum = 0.;
+        let mut nn_count = 0;
+        for (entity, pos, _) in boids {
+            let mut radius = start_radius;
+            loop {
+                let nearest = neighbours(grid, *entity, *pos, Vec3::ZERO, Perception::all_around(radius), position)
+                    .map(|near| near.distance)
+                    .reduce(f32::min);
+                if let Some(distance) = nearest {
+                    nn_sum += distance;
+                    nn_count += 1;
+                    break;
+                }
+                if radius > max_radius {
+                    break;
+                }
+                radius *= 2.;
+            }
+        }
+
+        // Clusters as connected components, with union-find
+        let mut parent: Vec<usize> = (0..boids.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for (i, (entity, pos, _)) in boids.iter().enumerate() {
+            for near in neighbours(grid, *entity, *pos, Vec3::ZERO, Perception::all_around(link_radius), position) {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, index[&near.entity]));
+                if a != b {
+                    parent[a] = b;
+                }
+            }
+        }
+        let clusters = (0..boids.len()).filter(|&i| root(&mut parent, i) == i).count();
+
+        let occupied_cells = grid.cells().filter(|cell| !cell.is_empty()).count();
+        let max_cell_occupancy = grid.cells().map(|cell| cell.len()).max().unwrap_or(0);
+
+        Some(Self {
+            polarization: heading_sum.length() / n,
+            mean_speed: speed_sum / n,
+            mean_nearest_neighbour: if nn_count > 0 { nn_sum / nn_count as f32 } else { 0. },
+            centroid,
+            radius: gyration.sqrt(),
+            clusters,
+            occupied_cells,
+            max_cell_occupancy,
+        })
+    }
+}
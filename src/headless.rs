@@ -0,0 +1,46 @@
+//! Runs the flock simulation without a window, GPU, assets or audio,
+//! so it can be stepped from tests and benchmarks on machines without a display.
+//!
+//! ```
+//! use bevy_boid_birds::headless::{headless_app, flock_state, BoidSettings};
+//!
+//! let settings = BoidSettings { bird_count: 50, deterministic: true, ..Default::default() };
+//! let mut app = headless_app(settings);
+//! for _ in 0..10 {
+//!     app.update();
+//! }
+//! assert_eq!(flock_state(&mut app).len(), 50);
+//! ```
+
+use bevy::prelude::*;
+
+use crate::boids::BoidSimulationPlugin;
+use crate::GameState;
+
+pub use crate::boids::{Boid, BoidSettings, BoundaryMode, FlockControl, FlockSave, FlockSnapshot, GridMap, LoadFlock, Obstacle, Perception, Predator, ResizeFlock, SaveFlock, SimulationClock, SimulationStep, Species, SpeciesTable, SteeringWeights, TargetVelocity, Velocity};
+pub use crate::diagnostics::{FlockDiagnosticsPlugin, FlockMetrics};
+pub use crate::recorder::{FlockRecorder, FlockRecorderPlugin};
+pub use crate::ron_file::RonFileError;
+
+/// An [`App`] with [`MinimalPlugins`] and the boid simulation only.
+/// Every [`App::update`] advances the simulation by one step, however long it took, see [`SimulationClock::lockstep`].
+pub fn headless_app(settings: BoidSettings) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(settings)
+        .insert_resource(SimulationClock::lockstep())
+        .add_state(GameState::Playing)
+        .add_plugin(BoidSimulationPlugin);
+    app
+}
+
+/// Position and velocity of every boid, ordered by entity so runs can be compared with each other.
+pub fn flock_state(app: &mut App) -> Vec<(Entity, Vec3, Vec3)> {
+    let mut state: Vec<_> = app.world
+        .query_filtered::<(Entity, &Transform, &Velocity), With<Boid>>()
+        .iter(&app.world)
+        .map(|(entity, transform, velocity)| (entity, transform.translation, velocity.0))
+        .collect();
+    state.sort_by_key(|(entity, ..)| *entity);
+    state
+}
@@ -0,0 +1,169 @@
+use bevy::prelude::*;
+
+use crate::boids::{
+    Boid, BoidSettings, BoidSystem, FlockSnapshot, GridMap, Species, SpeciesRelation, SpeciesTable,
+    SteeringForce, SteeringRule, TargetVelocity, Velocity,
+};
+use crate::loading::FontAssets;
+use crate::selection::{SelectedBoid, PICK_RADIUS};
+use crate::GameState;
+
+/// Highlights the [`SelectedBoid`] and shows what is steering it in a panel,
+/// since the world inspector lists every boid under the same name.
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Playing).with_system(setup_inspector))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(highlight_selection.after(BoidSystem::Move))
+                    .with_system(update_inspector_panel.after(BoidSystem::Integrate))
+            );
+    }
+}
+
+/// Sphere drawn around the selected boid, the size it can be clicked within
+#[derive(Component)]
+struct SelectionHighlight;
+
+#[derive(Component)]
+struct InspectorPanel;
+
+#[derive(Component)]
+struct InspectorText;
+
+fn setup_inspector(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    font_assets: Res<FontAssets>,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::UVSphere { radius: PICK_RADIUS, ..default() })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(1.0, 0.85, 0.2, 0.3),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        },
+        SelectionHighlight,
+        Name::new("Selection Highlight"),
+    ));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect { top: Val::Px(10.0), right: Val::Px(10.0), ..default() },
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.7).into(),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            },
+            // Lets clicks on the panel be told apart from clicks on the flock
+            Interaction::default(),
+            InspectorPanel,
+            Name::new("Inspector Panel"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 16.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                ),
+                InspectorText,
+            ));
+        });
+}
+
+fn highlight_selection(
+    selected: Res<SelectedBoid>,
+    q_boids: Query<&Transform, (With<Boid>, Without<SelectionHighlight>)>,
+    mut q_highlight: Query<(&mut Transform, &mut Visibility), With<SelectionHighlight>>,
+) {
+    let target = selected.0.and_then(|entity| q_boids.get(entity).ok());
+
+    for (mut trans, mut visibility) in q_highlight.iter_mut() {
+        visibility.is_visible = target.is_some();
+        if let Some(boid_trans) = target {
+            trans.translation = boid_trans.translation;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_inspector_panel(
+    selected: Res<SelectedBoid>,
+    snapshot: Res<FlockSnapshot>,
+    grid: Res<GridMap>,
+    settings: Res<BoidSettings>,
+    table: Res<SpeciesTable>,
+    q_boids: Query<(&Species, &Velocity, &TargetVelocity, &SteeringForce), With<Boid>>,
+    mut q_panel: Query<&mut Visibility, With<InspectorPanel>>,
+    mut q_text: Query<&mut Text, With<InspectorText>>,
+) {
+    let boid = selected.0.and_then(|entity| Some((entity, q_boids.get(entity).ok()?)));
+
+    for mut visibility in q_panel.iter_mut() {
+        visibility.is_visible = boid.is_some();
+    }
+    let (entity, (species, vel, target, steering)) = match boid {
+        Some(boid) => boid,
+        None => return,
+    };
+
+    let (seen, flockmates) = count_neighbours(&snapshot, &grid, &settings, &table, entity);
+
+    let mut lines = vec![
+        format!("{:?} {:?}", species, entity),
+        format!("Velocity: {}", describe(vel.0)),
+        format!("Target velocity: {}", describe(target.0)),
+        format!("Neighbours: {} seen, {} flockmates", seen, flockmates),
+        String::new(),
+        "Steering".to_string(),
+    ];
+    lines.extend(SteeringRule::ALL.iter().map(|rule| format!("{:?}: {}", rule, describe(steering.contribution(*rule)))));
+    lines.push(format!("Total: {}", describe(steering.total)));
+
+    if let Ok(mut text) = q_text.get_single_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+/// Boids `entity` perceived at the start of the step, and how many of those it flocks with
+fn count_neighbours(
+    snapshot: &FlockSnapshot,
+    grid: &GridMap,
+    settings: &BoidSettings,
+    table: &SpeciesTable,
+    entity: Entity,
+) -> (usize, usize) {
+    let sample = match snapshot.boids().iter().find(|sample| sample.entity == entity) {
+        Some(sample) => sample,
+        // Spawned this step
+        None => return (0, 0),
+    };
+    let perception = settings.perception_for(table.params(sample.species));
+
+    snapshot.neighbours(grid, entity, sample.position, sample.velocity, perception)
+        .fold((0, 0), |(seen, flockmates), (_, other)| {
+            let flocks = table.relation(sample.species, other.species) == SpeciesRelation::Flock;
+            (seen + 1, flockmates + flocks as usize)
+        })
+}
+
+fn describe(v: Vec3) -> String {
+    format!("({:.2}, {:.2}, {:.2})  |{:.2}|", v.x, v.y, v.z, v.length())
+}
@@ -2,21 +2,31 @@ mod actions;
 mod audio;
 mod loading;
 mod menu;
-mod player;
 mod scene;
 mod camera;
 mod boids;
 mod debugger;
+mod diagnostics;
+mod recorder;
+mod selection;
+mod inspector;
+mod ron_file;
+pub mod headless;
 
 use crate::actions::ActionsPlugin;
 use crate::audio::InternalAudioPlugin;
 use crate::loading::LoadingPlugin;
 use crate::menu::MenuPlugin;
-use crate::player::PlayerPlugin;
 use crate::scene::ScenePlugin;
 use crate::camera::CameraPlugin;
 use crate::boids::BoidsPlugin;
 use crate::debugger::DebugPlugin;
+use crate::diagnostics::FlockDiagnosticsPlugin;
+use crate::recorder::{FlockRecorderPlugin, RecordingHotkeyPlugin};
+use crate::selection::SelectionPlugin;
+use crate::inspector::InspectorPlugin;
+
+pub use crate::boids::BirdRendering;
 
 use bevy::app::App;
 #[cfg(debug_assertions)]
@@ -44,13 +54,17 @@ impl Plugin for GamePlugin {
         app.add_state(GameState::Loading)
             .add_plugin(LoadingPlugin)
             .add_plugin(MenuPlugin)
-            .add_plugin(ActionsPlugin)
+            .add_plugin(ActionsPlugin { write_default_bindings: true })
             .add_plugin(InternalAudioPlugin)
-            .add_plugin(PlayerPlugin)
             .add_plugin(ScenePlugin)
             .add_plugin(CameraPlugin)
+            .add_plugin(SelectionPlugin)
+            .add_plugin(InspectorPlugin)
             .add_plugin(BoidsPlugin)
             .add_plugin(DebugPlugin)
+            .add_plugin(FlockDiagnosticsPlugin::default())
+            .add_plugin(FlockRecorderPlugin::default())
+            .add_plugin(RecordingHotkeyPlugin)
             
             // External
             .add_plugin(bevy_inspector_egui::quick::WorldInspectorPlugin)
@@ -58,8 +72,8 @@ impl Plugin for GamePlugin {
 
         #[cfg(debug_assertions)]
         {
-            // app.add_plugin(FrameTimeDiagnosticsPlugin::default())
-            //     .add_plugin(LogDiagnosticsPlugin::default());
+            app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+                .add_plugin(LogDiagnosticsPlugin::default());
         }
     }
 }
@@ -14,7 +14,6 @@ impl Plugin for LoadingPlugin {
             LoadingState::new(GameState::Loading)
                 .with_collection::<FontAssets>()
                 .with_collection::<AudioAssets>()
-                .with_collection::<TextureAssets>()
                 .with_collection::<SceneAssets>()
                 .continue_to_state(GameState::Menu),
         );
@@ -28,6 +27,18 @@ impl Plugin for LoadingPlugin {
 pub struct SceneAssets {
     #[asset(path = "models/bird_flight_animation.glb#Scene0")]
     pub bird: Handle<Scene>,
+    #[asset(path = "models/dove_bird_rigged.glb#Scene0")]
+    pub pigeon: Handle<Scene>,
+    #[asset(path = "models/bird_flight_animation.glb#Mesh0/Primitive0")]
+    pub bird_mesh: Handle<Mesh>,
+    #[asset(path = "models/dove_bird_rigged.glb#Mesh0/Primitive0")]
+    pub pigeon_mesh: Handle<Mesh>,
+    #[asset(path = "models/dove_bird_rigged.glb#Scene0")]
+    pub hawk: Handle<Scene>,
+    #[asset(path = "models/bird_flight_animation.glb#Animation0")]
+    pub bird_flight: Handle<AnimationClip>,
+    #[asset(path = "models/dove_bird_rigged.glb#Animation0")]
+    pub pigeon_flight: Handle<AnimationClip>,
 }
 
 #[derive(AssetCollection, Resource)]
@@ -41,9 +52,3 @@ pub struct AudioAssets {
     #[asset(path = "audio/flying.ogg")]
     pub flying: Handle<AudioSource>,
 }
-
-#[derive(AssetCollection, Resource)]
-pub struct TextureAssets {
-    #[asset(path = "textures/bevy.png")]
-    pub texture_bevy: Handle<Image>,
-}
@@ -5,7 +5,7 @@ use bevy::prelude::*;
 use bevy::window::WindowId;
 use bevy::winit::WinitWindows;
 use bevy::DefaultPlugins;
-use bevy_boid_birds::GamePlugin;
+use bevy_boid_birds::{BirdRendering, GamePlugin};
 use std::io::Cursor;
 use winit::window::Icon;
 
@@ -23,6 +23,8 @@ fn main() {
             },
             ..default()
         }))
+        // Pass `--scenes` to draw every bird as its own glTF scene, for close-up shots
+        .insert_resource(if std::env::args().any(|arg| arg == "--scenes") { BirdRendering::Scenes } else { BirdRendering::Instanced })
         .add_plugin(GamePlugin)
         .add_startup_system(set_window_icon)
         .run();
@@ -0,0 +1,226 @@
+//! Streams boid trajectories to CSV files for offline analysis.
+//!
+//! Every recording writes two files to [`FlockRecorder::directory`]:
+//!
+//! - `flock_NNN.csv` with one row per boid and sample:
+//!   `step,time,entity,species,x,y,z,vx,vy,vz`
+//! - `flock_NNN_settings.csv` with the [`BoidSettings`] in force from each `step` on,
+//!   written when the recording starts and whenever the settings change
+//!
+//! `time` is simulated seconds since the recording started and `entity` is [`Entity::to_bits`],
+//! which stays unique for the whole run even when boids are respawned.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use crate::actions::{set_actions, Actions};
+use crate::boids::{Boid, BoidSettings, BoidSystem, SimulationStep, SimulationSteps, Species, Velocity};
+use crate::GameState;
+
+/// Records the flock while [`FlockRecorder::recording`] is set. Needs only the simulation,
+/// so it can be added to [`headless_app`](crate::headless::headless_app) as well.
+pub struct FlockRecorderPlugin {
+    /// Simulated seconds between samples, 0 samples every step
+    pub interval: f32,
+    pub directory: PathBuf,
+}
+
+impl Default for FlockRecorderPlugin {
+    fn default() -> Self {
+        Self { interval: 0.1, directory: "recordings".into() }
+    }
+}
+
+impl Plugin for FlockRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FlockRecorder {
+                recording: false,
+                interval: self.interval,
+                directory: self.directory.clone(),
+                session: None,
+            })
+            // Once per step, so samples follow simulated rather than frame time
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(SimulationSteps)
+                    .with_system(record_flock.after(BoidSystem::Move))
+            );
+    }
+}
+
+/// Starts and stops recording with the [`Actions::toggle_recording`] hotkey. Separate from
+/// [`FlockRecorderPlugin`], which has to work without input in a headless app.
+pub struct RecordingHotkeyPlugin;
+
+impl Plugin for RecordingHotkeyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(recording_hotkey.after(set_actions))
+        );
+    }
+}
+
+/// Set `recording` to start or stop a recording. A new pair of files is started every time.
+#[derive(Resource)]
+pub struct FlockRecorder {
+    pub recording: bool,
+    /// Simulated seconds between samples, 0 samples every step
+    pub interval: f32,
+    pub directory: PathBuf,
+    session: Option<Recording>,
+}
+
+impl FlockRecorder {
+    /// Path of the boid file being written, if recording
+    pub fn current_file(&self) -> Option<&PathBuf> {
+        self.session.as_ref().map(|session| &session.path)
+    }
+}
+
+struct Recording {
+    path: PathBuf,
+    boids: BufWriter<File>,
+    settings: BufWriter<File>,
+    elapsed: f32,
+    until_sample: f32,
+}
+
+impl Recording {
+    /// Opens the first unused `flock_NNN` pair of files in `directory`.
+    fn start(directory: &Path) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let (path, settings_path) = (0..)
+            .map(|take| (
+                directory.join(format!("flock_{:03}.csv", take)),
+                directory.join(format!("flock_{:03}_settings.csv", take)),
+            ))
+            .find(|(path, _)| !path.exists())
+            .unwrap();
+
+        let mut boids = BufWriter::new(File::create(&path)?);
+        writeln!(boids, "step,time,entity,species,x,y,z,vx,vy,vz")?;
+
+        let mut settings = BufWriter::new(File::create(settings_path)?);
+        writeln!(
+            settings,
+            "step,time,speed,min_speed,max_speed,max_force,steering_factor,bird_count,\
+            separation_distance,perception_radius,field_of_view,\
+            bounds_min_x,bounds_min_y,bounds_min_z,bounds_max_x,bounds_max_y,bounds_max_z,\
+            dimensions_x,dimensions_y,dimensions_z,boundary,wall_margin,arena_radius,\
+            predator_count,predator_speed,predator_max_force,hunt_radius,flee_radius,\
+            flight_physics,max_acceleration,drag,max_bank,pitch_rate,roll_rate,\
+            obstacle_look_ahead,obstacle_margin,deterministic,timestep,seed"
+        )?;
+
+        Ok(Self { path, boids, settings, elapsed: 0., until_sample: 0. })
+    }
+
+    fn write_settings(&mut self, step: u64, s: &BoidSettings) -> io::Result<()> {
+        let [min, max] = s.bounds;
+        let [dx, dy, dz] = s.dimensions;
+        writeln!(
+            self.settings,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{:?},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            step, self.elapsed, s.speed, s.min_speed, s.max_speed, s.max_force, s.steering_factor, s.bird_count,
+            s.separation_distance, s.perception_radius, s.field_of_view,
+            min.x, min.y, min.z, max.x, max.y, max.z,
+            dx, dy, dz, s.boundary, s.wall_margin, s.arena_radius,
+            s.predator_count, s.predator_speed, s.predator_max_force, s.hunt_radius, s.flee_radius,
+            s.flight_physics, s.max_acceleration, s.drag, s.max_bank, s.pitch_rate, s.roll_rate,
+            s.obstacle_look_ahead, s.obstacle_margin, s.deterministic, s.timestep, s.seed,
+        )
+    }
+
+    fn write_boids(&mut self, step: u64, boids: &[(Entity, Species, Vec3, Vec3)]) -> io::Result<()> {
+        for (entity, species, pos, vel) in boids {
+            writeln!(
+                self.boids,
+                "{},{},{},{:?},{},{},{},{},{},{}",
+                step, self.elapsed, entity.to_bits(), species, pos.x, pos.y, pos.z, vel.x, vel.y, vel.z,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<PathBuf> {
+        self.boids.flush()?;
+        self.settings.flush()?;
+        Ok(self.path)
+    }
+}
+
+fn record_flock (
+    mut recorder: ResMut<FlockRecorder>,
+    step: Res<SimulationStep>,
+    settings: Res<BoidSettings>,
+    q_boids: Query<(Entity, &Species, &Transform, &Velocity), With<Boid>>,
+) {
+    let recorder = &mut *recorder;
+
+    if !recorder.recording {
+        if let Some(session) = recorder.session.take() {
+            match session.finish() {
+                Ok(path) => info!("Stopped recording to {}", path.display()),
+                Err(err) => error!("Could not finish the recording: {}", err),
+            }
+        }
+        return;
+    }
+
+    let started = recorder.session.is_none();
+    if started {
+        match Recording::start(&recorder.directory) {
+            Ok(session) => {
+                info!("Recording the flock to {}", session.path.display());
+                recorder.session = Some(session);
+            }
+            Err(err) => {
+                error!("Could not start recording: {}", err);
+                recorder.recording = false;
+                return;
+            }
+        }
+    }
+    let session = recorder.session.as_mut().unwrap();
+
+    let result = (|| {
+        if started || settings.is_changed() {
+            session.write_settings(step.count, &settings)?;
+        }
+
+        if session.until_sample <= 0. {
+            let mut boids: Vec<_> = q_boids.iter()
+                .map(|(entity, species, trans, vel)| (entity, *species, trans.translation, vel.0))
+                .collect();
+            boids.sort_by_key(|(entity, ..)| *entity);
+            session.write_boids(step.count, &boids)?;
+            session.until_sample += recorder.interval;
+        }
+
+        session.elapsed += step.delta;
+        session.until_sample -= step.delta;
+        Ok::<_, io::Error>(())
+    })();
+
+    if let Err(err) = result {
+        error!("Stopped recording after a write error: {}", err);
+        recorder.session = None;
+        recorder.recording = false;
+    }
+}
+
+/// Starts or stops recording from [`Actions::toggle_recording`].
+fn recording_hotkey (
+    actions: Res<Actions>,
+    mut recorder: ResMut<FlockRecorder>,
+) {
+    if actions.toggle_recording {
+        recorder.recording = !recorder.recording;
+    }
+}
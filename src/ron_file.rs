@@ -0,0 +1,47 @@
+//! Reading and writing the RON files the app keeps, such as the input bindings and saved flocks.
+
+use std::{fmt, fs, io, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+#[derive(Debug)]
+pub enum RonFileError {
+    Io(io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+}
+
+impl fmt::Display for RonFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RonFileError::Io(err) => write!(f, "could not access the file: {}", err),
+            RonFileError::Serialize(err) => write!(f, "could not write RON: {}", err),
+            RonFileError::Deserialize(err) => write!(f, "could not read RON: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RonFileError {}
+
+impl From<io::Error> for RonFileError {
+    fn from(err: io::Error) -> Self {
+        RonFileError::Io(err)
+    }
+}
+
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, RonFileError> {
+    ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()).map_err(RonFileError::Serialize)
+}
+
+pub fn from_str<T: DeserializeOwned>(text: &str) -> Result<T, RonFileError> {
+    ron::from_str(text).map_err(RonFileError::Deserialize)
+}
+
+pub fn save<T: Serialize>(value: &T, path: &Path) -> Result<(), RonFileError> {
+    fs::write(path, to_string(value)?)?;
+    Ok(())
+}
+
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, RonFileError> {
+    from_str(&fs::read_to_string(path)?)
+}
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use bevy_atmosphere::prelude::AtmospherePlugin;
-use crate::GameState;
+use crate::{GameState, boids::Obstacle};
 
 pub struct ScenePlugin;
 
@@ -34,16 +34,20 @@ fn setup_scene(
                 transform: Transform::from_xyz(0., -40., 0.),
                 ..default()
         },
+        Obstacle::Plane { normal: Vec3::Y, offset: box_height },
         Name::new("Ground")
     ));
 
     // cube
-    commands.spawn(PbrBundle {
-        mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
-        material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
-        transform: Transform::from_xyz(0.0, 1., 0.0),
-        ..default()
-    });
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+            material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
+            transform: Transform::from_xyz(0.0, 1., 0.0),
+            ..default()
+        },
+        Obstacle::Aabb { half_extents: Vec3::splat(0.5) },
+    ));
     // light
     commands.spawn(PointLightBundle {
         point_light: PointLight {
@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy::window::CursorGrabMode;
+
+use crate::actions::{ControlInput, GameControl};
+use crate::boids::{Boid, BoidSystem, GridMap};
+use crate::GameState;
+
+/// Lets the player pick one boid, by clicking it or cycling through the flock,
+/// for the chase and first-person cameras to follow.
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedBoid>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(forget_despawned_boid)
+                    .with_system(click_to_select.after(forget_despawned_boid).after(BoidSystem::Move))
+                    .with_system(cycle_selection.after(forget_despawned_boid))
+            );
+    }
+}
+
+/// The boid the player picked, if any.
+#[derive(Resource, Default, Debug)]
+pub struct SelectedBoid(pub Option<Entity>);
+
+/// How far from the pointer's ray a boid can be and still be clicked
+pub const PICK_RADIUS: f32 = 1.5;
+
+/// World space ray through the pointer, from the camera into the scene.
+/// While the fly camera holds the cursor, the pointer is the centre of the screen.
+pub fn pointer_ray(camera: &Camera, camera_transform: &GlobalTransform, window: &Window) -> Option<(Vec3, Vec3)> {
+    let size = Vec2::new(window.width(), window.height());
+    let pointer = match window.cursor_grab_mode() {
+        CursorGrabMode::None => window.cursor_position()?,
+        _ => size / 2.,
+    };
+
+    // Normalized device coordinates, where bevy puts the near plane at depth 1 and the far plane at 0
+    let ndc = pointer / size * 2. - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    let near = ndc_to_world.project_point3(ndc.extend(1.));
+    let far = ndc_to_world.project_point3(ndc.extend(f32::EPSILON));
+
+    Some((near, (far - near).try_normalize()?))
+}
+
+/// The boid closest to the camera among those within [`PICK_RADIUS`] of the ray,
+/// looking only in the grid cells the ray passes through.
+pub fn pick_boid<F>(grid: &GridMap, origin: Vec3, direction: Vec3, position: F) -> Option<Entity>
+where
+    F: Fn(Entity) -> Option<Vec3>,
+{
+    grid.indices_along_ray(origin, direction, PICK_RADIUS)
+        .into_iter()
+        .flat_map(|index| grid.query_cell(index).iter().copied())
+        .filter_map(|entity| {
+            let offset = position(entity)? - origin;
+            let along = offset.dot(direction);
+            let off_ray = (offset - direction * along).length();
+            (along > 0. && off_ray < PICK_RADIUS).then_some((entity, along))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity)
+}
+
+fn forget_despawned_boid(
+    mut selected: ResMut<SelectedBoid>,
+    q_boids: Query<(), With<Boid>>,
+) {
+    if let Some(entity) = selected.0 {
+        if q_boids.get(entity).is_err() {
+            selected.0 = None;
+        }
+    }
+}
+
+fn click_to_select(
+    input: ControlInput,
+    windows: Res<Windows>,
+    grid: Res<GridMap>,
+    mut selected: ResMut<SelectedBoid>,
+    q_camera: Query<(&Camera, &GlobalTransform)>,
+    q_boids: Query<&Transform, With<Boid>>,
+) {
+    // Clicks on the inspector never reach here, ControlInput leaves them to the UI
+    if !input.just_pressed(GameControl::SelectBird) {
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let ray = q_camera.iter()
+        .find(|(camera, _)| camera.is_active)
+        .and_then(|(camera, transform)| pointer_ray(camera, transform, window));
+
+    if let Some((origin, direction)) = ray {
+        let position = |entity| q_boids.get(entity).ok().map(|trans| trans.translation);
+        // Clicking empty sky clears the selection
+        selected.0 = pick_boid(&grid, origin, direction, position);
+    }
+}
+
+/// Steps the selection through the flock in entity order.
+fn cycle_selection(
+    input: ControlInput,
+    mut selected: ResMut<SelectedBoid>,
+    q_boids: Query<Entity, With<Boid>>,
+) {
+    let step: isize = if input.just_pressed(GameControl::NextBird) {
+        1
+    } else if input.just_pressed(GameControl::PreviousBird) {
+        -1
+    } else {
+        return;
+    };
+
+    let mut boids: Vec<Entity> = q_boids.iter().collect();
+    if boids.is_empty() {
+        return;
+    }
+    boids.sort();
+
+    let count = boids.len() as isize;
+    let next = match selected.0.and_then(|entity| boids.binary_search(&entity).ok()) {
+        Some(current) => (current as isize + step).rem_euclid(count),
+        None if step > 0 => 0,
+        None => count - 1,
+    };
+    selected.0 = Some(boids[next as usize]);
+}
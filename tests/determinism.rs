@@ -0,0 +1,29 @@
+use bevy_boid_birds::headless::{flock_state, headless_app, BoidSettings};
+
+fn run(seed: u64, updates: usize) -> Vec<(bevy::prelude::Entity, bevy::prelude::Vec3, bevy::prelude::Vec3)> {
+    let settings = BoidSettings { bird_count: 300, predator_count: 1, deterministic: true, seed, ..Default::default() };
+    let mut app = headless_app(settings);
+    for _ in 0..updates {
+        app.update();
+    }
+    flock_state(&mut app)
+}
+
+#[test]
+fn same_seed_gives_identical_trajectories() {
+    let a = run(7, 120);
+    let b = run(7, 120);
+
+    assert_eq!(a.len(), 300);
+    // Bit for bit, not approximately
+    assert_eq!(a, b);
+}
+
+#[test]
+fn different_seeds_give_different_trajectories() {
+    let a = run(7, 120);
+    let b = run(8, 120);
+
+    assert_eq!(a.len(), b.len());
+    assert_ne!(a, b);
+}
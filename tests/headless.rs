@@ -0,0 +1,240 @@
+use bevy::diagnostic::Diagnostics;
+use bevy::prelude::*;
+use bevy_boid_birds::headless::{
+    flock_state, headless_app, BoidSettings, BoundaryMode, FlockDiagnosticsPlugin, FlockSave, GridMap, LoadFlock,
+    Obstacle, Predator, ResizeFlock, SaveFlock, SimulationClock, SimulationStep,
+};
+
+const MIN: f32 = -40.;
+const MAX: f32 = 40.;
+
+fn settings(boundary: BoundaryMode) -> BoidSettings {
+    BoidSettings {
+        bird_count: 300,
+        predator_count: 0,
+        deterministic: true,
+        seed: 9,
+        boundary,
+        bounds: [Vec3::splat(MIN), Vec3::splat(MAX)],
+        arena_radius: MAX,
+        wall_margin: 8.,
+        dimensions: [8, 8, 8],
+        ..Default::default()
+    }
+}
+
+/// Fails unless every boid sits in the grid cell of its position, and nothing else is in the grid
+fn assert_grid_matches(app: &mut App) {
+    let state = flock_state(app);
+    let grid = app.world.resource::<GridMap>();
+    assert_eq!(grid.len(), state.len());
+    for (entity, pos, _) in &state {
+        assert!(grid.query_cell(grid.cell_index(*pos)).contains(entity), "{:?} at {} is not in its cell", entity, pos);
+    }
+}
+
+#[test]
+fn hard_boundaries_keep_the_flock_inside() {
+    for mode in [BoundaryMode::Wrap, BoundaryMode::Bounce, BoundaryMode::Sphere, BoundaryMode::Cylinder] {
+        // Predators are confined as well, but must stay out of the grid
+        let mut app = headless_app(BoidSettings { predator_count: 2, ..settings(mode) });
+        for _ in 0..400 {
+            app.update();
+        }
+
+        let state = flock_state(&mut app);
+        assert_eq!(state.len(), 300);
+        let predators: Vec<Vec3> = app.world.query_filtered::<&Transform, With<Predator>>()
+            .iter(&app.world)
+            .map(|trans| trans.translation)
+            .collect();
+        assert_eq!(predators.len(), 2);
+
+        for pos in state.iter().map(|(_, pos, _)| pos).chain(&predators) {
+            let inside = match mode {
+                BoundaryMode::Sphere => pos.length() <= MAX + 1e-3,
+                BoundaryMode::Cylinder => Vec2::new(pos.x, pos.z).length() <= MAX + 1e-3 && (MIN..=MAX).contains(&pos.y),
+                _ => pos.cmpge(Vec3::splat(MIN)).all() && pos.cmple(Vec3::splat(MAX)).all(),
+            };
+            assert!(inside, "{:?} let a bird out to {}", mode, pos);
+        }
+        assert_grid_matches(&mut app);
+    }
+}
+
+#[test]
+fn floors_hold_up_boids_and_predators() {
+    let mut app = headless_app(BoidSettings { predator_count: 2, ..settings(BoundaryMode::Bounce) });
+    // Across the middle of the bounds, so half the birds spawn under it
+    app.world.spawn((Obstacle::Plane { normal: Vec3::Y, offset: 0. }, Transform::default()));
+    for _ in 0..60 {
+        app.update();
+    }
+
+    let predators: Vec<Vec3> = app.world.query_filtered::<&Transform, With<Predator>>()
+        .iter(&app.world)
+        .map(|trans| trans.translation)
+        .collect();
+    assert_eq!(predators.len(), 2);
+    for pos in flock_state(&mut app).iter().map(|(_, pos, _)| pos).chain(&predators) {
+        assert!(pos.y >= -1e-3, "a bird is under the floor at {}", pos);
+    }
+    assert_grid_matches(&mut app);
+}
+
+#[test]
+fn soft_walls_keep_most_of_the_flock_inside() {
+    let mut app = headless_app(settings(BoundaryMode::SoftWalls));
+    for _ in 0..400 {
+        app.update();
+    }
+
+    // Walls only steer, so stragglers can be caught outside, but none may fly off for good
+    let state = flock_state(&mut app);
+    let inside = state.iter().filter(|(_, pos, _)| pos.abs().max_element() <= MAX).count();
+    assert!(inside * 10 >= state.len() * 9, "only {} of {} boids are inside", inside, state.len());
+    for (_, pos, _) in &state {
+        assert!(pos.abs().max_element() <= 2. * MAX, "a boid escaped to {}", pos);
+    }
+    assert_grid_matches(&mut app);
+}
+
+#[test]
+fn resize_flock_changes_the_count() {
+    let mut app = headless_app(settings(BoundaryMode::SoftWalls));
+    app.update();
+    assert_eq!(flock_state(&mut app).len(), 300);
+
+    app.world.send_event(ResizeFlock(50));
+    app.update();
+    assert_eq!(flock_state(&mut app).len(), 350);
+    assert_eq!(app.world.resource::<BoidSettings>().bird_count, 350);
+
+    app.world.send_event(ResizeFlock(-200));
+    app.update();
+    assert_eq!(flock_state(&mut app).len(), 150);
+    assert_eq!(app.world.resource::<BoidSettings>().bird_count, 150);
+    assert_grid_matches(&mut app);
+}
+
+#[test]
+fn changing_the_predator_count_keeps_the_other_predators() {
+    let mut app = headless_app(BoidSettings { predator_count: 3, ..settings(BoundaryMode::SoftWalls) });
+    app.update();
+    let predators = |app: &mut App| {
+        let mut predators: Vec<Entity> = app.world.query_filtered::<Entity, With<Predator>>().iter(&app.world).collect();
+        predators.sort();
+        predators
+    };
+    let before = predators(&mut app);
+    assert_eq!(before.len(), 3);
+
+    app.world.resource_mut::<BoidSettings>().predator_count = 4;
+    app.update();
+    let grown = predators(&mut app);
+    assert_eq!(grown.len(), 4);
+    assert!(before.iter().all(|entity| grown.contains(entity)));
+
+    // The newest go first
+    app.world.resource_mut::<BoidSettings>().predator_count = 2;
+    app.update();
+    assert_eq!(predators(&mut app), before[..2]);
+}
+
+#[test]
+fn invalid_grid_settings_are_rejected() {
+    let mut app = headless_app(settings(BoundaryMode::SoftWalls));
+    app.update();
+    let entities: Vec<Entity> = flock_state(&mut app).into_iter().map(|(entity, ..)| entity).collect();
+
+    for dimensions in [[0, 8, 8], [8, -1, 8], [8, 8, i32::MAX]] {
+        app.world.resource_mut::<BoidSettings>().dimensions = dimensions;
+        app.update();
+        assert_eq!(app.world.resource::<BoidSettings>().dimensions, [8, 8, 8]);
+    }
+    app.world.resource_mut::<BoidSettings>().bounds = [Vec3::splat(MIN), Vec3::new(MAX, MIN, MAX)];
+    app.update();
+    assert_eq!(app.world.resource::<BoidSettings>().bounds, [Vec3::splat(MIN), Vec3::splat(MAX)]);
+
+    // Neither the grid nor the flock was rebuilt
+    let after: Vec<Entity> = flock_state(&mut app).into_iter().map(|(entity, ..)| entity).collect();
+    assert_eq!(entities, after);
+    assert_eq!(app.world.resource::<GridMap>().dimensions(), IVec3::splat(8));
+    assert_grid_matches(&mut app);
+}
+
+#[test]
+fn diagnostics_are_measured_every_n_steps() {
+    // Steps so short that every frame runs several of them
+    let mut app = headless_app(BoidSettings { timestep: 1e-6, ..settings(BoundaryMode::SoftWalls) });
+    app.insert_resource(SimulationClock::default());
+    app.add_plugin(FlockDiagnosticsPlugin { every_n_steps: 4 });
+    for _ in 0..4 {
+        app.update();
+    }
+
+    let steps = app.world.resource::<SimulationStep>().count;
+    assert!(steps > 4, "only {} steps ran", steps);
+    let diagnostics = app.world.resource::<Diagnostics>();
+    let polarization = diagnostics.get(FlockDiagnosticsPlugin::POLARIZATION).unwrap();
+    assert_eq!(polarization.history_len() as u64, steps / 4);
+}
+
+#[test]
+fn saved_flock_loads_back_unchanged() {
+    let path = std::env::temp_dir().join(format!("flock-{}.ron", std::process::id()));
+    // Entities are respawned on load, so boids are compared by position and velocity alone
+    let sorted = |boids: Vec<(Vec3, Vec3)>| {
+        let mut boids: Vec<_> = boids.into_iter().map(|(pos, vel)| (pos.to_array(), vel.to_array())).collect();
+        boids.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        boids
+    };
+    let state = |app: &mut App| sorted(flock_state(app).into_iter().map(|(_, pos, vel)| (pos, vel)).collect());
+
+    let mut app = headless_app(settings(BoundaryMode::SoftWalls));
+    for _ in 0..30 {
+        app.update();
+    }
+    let before_save = state(&mut app);
+
+    // The file is written before the step of the update that handles the event
+    app.world.send_event(SaveFlock(path.clone()));
+    app.update();
+    let after_save = state(&mut app);
+    let save = FlockSave::load(&path).unwrap();
+    assert_eq!(sorted(save.boids.iter().map(|boid| (boid.position, boid.velocity)).collect()), before_save);
+
+    for _ in 0..30 {
+        app.update();
+    }
+    let before_load = state(&mut app);
+    assert_ne!(before_load, after_save);
+
+    // Loading also happens before the step, which then has to repeat the one taken after saving
+    app.world.send_event(LoadFlock(path.clone()));
+    app.update();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(state(&mut app), after_save);
+    assert_grid_matches(&mut app);
+
+    // And every step after it, which needs the neighbours summed up in the same order as before
+    for _ in 0..30 {
+        app.update();
+    }
+    assert_eq!(state(&mut app), before_load);
+}
+
+#[test]
+fn restored_grid_keeps_the_saved_order() {
+    let mut app = headless_app(BoidSettings { predator_count: 1, ..settings(BoundaryMode::Wrap) });
+    for _ in 0..60 {
+        app.update();
+    }
+
+    // In grid order, which moving boids between cells has shuffled away from spawn order
+    let order = |save: &FlockSave| save.boids.iter().map(|boid| (boid.position, boid.velocity)).collect::<Vec<_>>();
+    let save = FlockSave::capture(&mut app.world);
+    save.restore(&mut app.world);
+    assert_eq!(order(&FlockSave::capture(&mut app.world)), order(&save));
+    assert_grid_matches(&mut app);
+}